@@ -1,6 +1,10 @@
-use crate::bundles::lyon_rendering::{get_path_from_verts, LyonRenderBundle, UNIT_SQUARE_PATH};
+use crate::bundles::lyon_rendering::{
+    get_circle_path, get_path_from_verts, LyonRenderBundle, UNIT_SQUARE_PATH,
+};
+use crate::network::clock::ServerClock;
+use crate::network::is_server;
 use bevy::prelude::*;
-use bevy_prototype_lyon::prelude::{Path, ShapeBundle};
+use bevy_prototype_lyon::prelude::{Path, ShapeBundle, Stroke};
 use bevy_rapier2d::{
     prelude::{Collider, QueryFilter, RapierContext, Sensor},
     rapier::prelude::{CollisionEvent, ContactForceEvent},
@@ -8,15 +12,71 @@ use bevy_rapier2d::{
 use bevy_replicon::replication_core::AppReplicationExt;
 use serde::{Deserialize, Serialize};
 
+/// Seconds a ship can be outside the arena before it starts taking damage.
+pub const ZONE_GRACE_SECONDS: f32 = 3.0;
+/// Damage per second as soon as the grace period runs out.
+pub const ZONE_BASE_DAMAGE: f32 = 20.0;
+/// How much the damage per second goes up for every second spent outside after the grace period.
+pub const ZONE_DAMAGE_INCREASE: f32 = 20.0;
+pub const ZONE_MAX_DAMAGE: f32 = 150.0;
+
 #[derive(Component, Reflect, Debug, Default, Copy, Clone, Serialize, Deserialize)]
 pub struct ArenaResident {
     pub is_outside: bool,
     pub time_exited: f32,
 }
 
+/// When a player left the arena.
+/// Updated by the server when the player leaves or returns, and replicated so the owning client
+/// can count down and warn the player. Times are from the [`ServerClock`].
+#[derive(Component, Reflect, Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[reflect(Component, Default)]
+pub struct ZoneWarning {
+    pub is_outside: bool,
+    /// Only meaningful while outside.
+    pub exited_at: f32,
+}
+
+impl ZoneWarning {
+    pub fn seconds_outside(&self, now: f32) -> f32 {
+        if self.is_outside {
+            (now - self.exited_at).max(0.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Seconds left before the zone starts doing damage, zero once it has started.
+    pub fn seconds_until_damage(&self, now: f32) -> f32 {
+        (ZONE_GRACE_SECONDS - self.seconds_outside(now)).max(0.0)
+    }
+
+    /// Damage per second the zone is currently doing, the longer the player stays out the more it hurts.
+    pub fn damage_per_second(&self, now: f32) -> f32 {
+        if !self.is_outside || self.seconds_until_damage(now) > 0.0 {
+            return 0.0;
+        }
+        let overtime = self.seconds_outside(now) - ZONE_GRACE_SECONDS;
+        (ZONE_BASE_DAMAGE + overtime * ZONE_DAMAGE_INCREASE).min(ZONE_MAX_DAMAGE)
+    }
+}
+
+/// The team a player is on, players don't have one when playing free for all.
 #[derive(
-    Component, Reflect, Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize,
+    Component,
+    Reflect,
+    FromReflect,
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
 )]
+#[reflect(Component, Default)]
 pub enum Force {
     #[default]
     Red,
@@ -27,27 +87,217 @@ pub enum Force {
     None,
 }
 
+impl std::fmt::Display for Force {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::str::FromStr for Force {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "red" => Ok(Force::Red),
+            "yellow" => Ok(Force::Yellow),
+            "blue" => Ok(Force::Blue),
+            "pink" => Ok(Force::Pink),
+            "green" => Ok(Force::Green),
+            "none" => Ok(Force::None),
+            _ => Err(format!("{s} is not a team")),
+        }
+    }
+}
+
+impl Force {
+    pub fn color(&self) -> Color {
+        match self {
+            Force::Red => Color::RED,
+            Force::Yellow => Color::YELLOW,
+            Force::Blue => Color::BLUE,
+            Force::Pink => Color::PINK,
+            Force::Green => Color::GREEN,
+            Force::None => Color::WHITE,
+        }
+    }
+}
+
 ///Bundle to be replicated over the wire, Only the server should spawn these
 ///Any components in here should be .replicate::<ThisType>() and should contain
 ///all the info needed for the corresponding EnrichBundle to hydrate on clients.
 #[derive(Component, Default, Debug, Reflect, Copy, Clone, PartialEq, Serialize, Deserialize)]
 #[reflect(Component, Default)]
 pub struct Arena {
+    pub shape: ArenaShape,
+    /// For circles, x is the diameter.
     pub starting_size: Vec2,
     pub current_size: Vec2,
+    pub current_center: Vec2,
+    /// Where the safe zone is heading, shown to players so they can get there in time.
+    pub next_size: Vec2,
+    pub next_center: Vec2,
     pub time_spawned: f32,
+    /// Players on this force take less damage outside the arena.
     pub friendly_force: Force,
 }
 
+impl Arena {
+    /// Whether a point is inside the current safe zone.
+    pub fn contains(&self, point: Vec2) -> bool {
+        let offset = point - self.current_center;
+        match self.shape {
+            ArenaShape::Rectangle => {
+                offset.x.abs() <= self.current_size.x / 2.0
+                    && offset.y.abs() <= self.current_size.y / 2.0
+            }
+            ArenaShape::Circle => offset.length() <= self.current_size.x / 2.0,
+        }
+    }
+}
+
+#[derive(Reflect, Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArenaShape {
+    #[default]
+    Rectangle,
+    Circle,
+}
+
+impl ArenaShape {
+    pub fn path(&self, size: Vec2) -> Path {
+        match self {
+            ArenaShape::Rectangle => get_path_from_verts(&UNIT_SQUARE_PATH, size),
+            ArenaShape::Circle => get_circle_path(size.x / 2.0),
+        }
+    }
+
+    pub fn collider(&self, size: Vec2) -> Collider {
+        match self {
+            ArenaShape::Rectangle => Collider::cuboid(size.x / 2.0, size.y / 2.0),
+            ArenaShape::Circle => Collider::ball(size.x / 2.0),
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct ArenaBoundary;
 
+/// Outline of where the safe zone will be after the current or next shrink.
+#[derive(Component)]
+pub struct NextArenaBoundary;
+
+/// Controls how the arena shrinks over the round, only exists on the server.
+///
+/// Phases run one after the other, each waiting and then shrinking to a new zone. Once all phases
+/// are done the arena stays at the last zone.
+#[derive(Component, Debug, Clone, Deserialize)]
+pub struct ShrinkSchedule {
+    pub phases: Vec<ShrinkPhase>,
+    /// The arena never gets smaller than this, relative to its starting size.
+    #[serde(default)]
+    pub min_scale: f32,
+}
+
+impl Default for ShrinkSchedule {
+    /// Shrink to nothing over two minutes.
+    fn default() -> Self {
+        Self {
+            phases: vec![ShrinkPhase {
+                pause: 0.0,
+                duration: 120.0,
+                scale: 0.0,
+                center: Vec2::ZERO,
+                easing: Easing::Linear,
+            }],
+            min_scale: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ShrinkPhase {
+    /// Seconds to hold the current zone before shrinking.
+    #[serde(default)]
+    pub pause: f32,
+    /// Seconds the shrink takes, 0 to jump straight to the new zone.
+    #[serde(default)]
+    pub duration: f32,
+    /// Size of the zone at the end of this phase, relative to the starting size.
+    pub scale: f32,
+    /// Center of the zone at the end of this phase.
+    #[serde(default)]
+    pub center: Vec2,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Starts slow and speeds up.
+    EaseIn,
+    /// Starts fast and slows down.
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// A safe zone, relative to the arena's starting size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Zone {
+    pub scale: f32,
+    pub center: Vec2,
+}
+
+impl ShrinkSchedule {
+    /// The zone `elapsed` seconds into the round, and the zone it is heading to next.
+    pub fn zones_at(&self, elapsed: f32) -> (Zone, Zone) {
+        let mut from = Zone {
+            scale: 1.0,
+            center: Vec2::ZERO,
+        };
+        let mut time_left = elapsed;
+        for phase in self.phases.iter() {
+            let to = Zone {
+                scale: phase.scale.max(self.min_scale),
+                center: phase.center,
+            };
+            if time_left < phase.pause {
+                return (from, to);
+            }
+            time_left -= phase.pause;
+            if time_left < phase.duration {
+                let t = phase.easing.apply(time_left / phase.duration);
+                let current = Zone {
+                    scale: from.scale + (to.scale - from.scale) * t,
+                    center: from.center.lerp(to.center, t),
+                };
+                return (current, to);
+            }
+            time_left -= phase.duration;
+            from = to;
+        }
+        (from, from)
+    }
+}
+
 pub fn spawn_arena(mut cmds: Commands, arenas: Query<(&Arena, Entity), Added<Arena>>) {
     arenas.iter().for_each(|(arena, ent)| {
         let id = cmds
             .spawn(LyonRenderBundle {
                 shape_render: ShapeBundle {
-                    path: get_path_from_verts(&UNIT_SQUARE_PATH, arena.starting_size),
+                    path: arena.shape.path(arena.current_size),
                     ..default()
                 },
                 ..default()
@@ -55,39 +305,84 @@ pub fn spawn_arena(mut cmds: Commands, arenas: Query<(&Arena, Entity), Added<Are
             .insert(Name::new("Arena Boundary"))
             .insert(ArenaBoundary)
             .id();
+        let next_id = cmds
+            .spawn(LyonRenderBundle {
+                shape_render: ShapeBundle {
+                    path: arena.shape.path(arena.next_size),
+                    ..default()
+                },
+                stroke: Stroke::new(Color::rgba(1.0, 1.0, 1.0, 0.25), 2.0),
+                ..default()
+            })
+            .insert(Name::new("Next Arena Boundary"))
+            .insert(NextArenaBoundary)
+            .id();
 
         cmds.entity(ent)
-            .insert(SpatialBundle::from_transform(Transform::from_xyz(
-                0.0, 0.0, 0.0,
+            .insert(SpatialBundle::from_transform(Transform::from_translation(
+                arena.current_center.extend(0.0),
             )))
-            .insert((
-                Collider::cuboid(arena.starting_size.x / 2.0, arena.starting_size.y / 2.0),
-                Sensor,
-            ))
-            .add_child(id);
+            .insert((arena.shape.collider(arena.current_size), Sensor))
+            .add_child(id)
+            .add_child(next_id);
     });
 }
 
 pub fn update_arena_size(
-    mut arenas: Query<(&mut Collider, &Arena), Changed<Arena>>,
-    mut boundary_walls: Query<&mut Path, With<ArenaBoundary>>,
+    mut arenas: Query<(&mut Collider, &mut Transform, &Arena), Changed<Arena>>,
+    mut boundary_walls: Query<&mut Path, (With<ArenaBoundary>, Without<NextArenaBoundary>)>,
+    mut next_boundaries: Query<
+        (&mut Path, &mut Transform, &mut Visibility),
+        (With<NextArenaBoundary>, Without<Arena>),
+    >,
 ) {
-    arenas.iter_mut().for_each(|(mut collider, arena)| {
-        let Ok(mut wall) = boundary_walls.get_single_mut() else {
-            warn!("Could not find boundary wall");
-            return;
-        };
+    arenas
+        .iter_mut()
+        .for_each(|(mut collider, mut transform, arena)| {
+            let Ok(mut wall) = boundary_walls.get_single_mut() else {
+                warn!("Could not find boundary wall");
+                return;
+            };
 
-        *wall = get_path_from_verts(&UNIT_SQUARE_PATH, arena.current_size);
-        *collider = Collider::cuboid(arena.current_size.x / 2.0, arena.current_size.y / 2.0);
-    });
+            *wall = arena.shape.path(arena.current_size);
+            *collider = arena.shape.collider(arena.current_size);
+            transform.translation = arena.current_center.extend(transform.translation.z);
+
+            if let Ok((mut next_wall, mut next_transform, mut visibility)) =
+                next_boundaries.get_single_mut()
+            {
+                let shrinking = arena.next_size != arena.current_size
+                    || arena.next_center != arena.current_center;
+                *visibility = if shrinking {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                };
+                *next_wall = arena.shape.path(arena.next_size);
+                // The boundary is a child of the arena, so this is relative to the current center
+                next_transform.translation = (arena.next_center - arena.current_center).extend(0.0);
+            }
+        });
 }
 
-pub fn shrink_arena(mut arenas: Query<&mut Arena>, time: Res<Time>) {
-    arenas.iter_mut().for_each(|mut arena| {
-        arena.current_size = (arena.current_size
-            - (1.0 / 120.0 * time.delta_seconds() * arena.starting_size))
-            .max(Vec2::splat(0.001));
+/// Runs on the server, clients get the new size through replication.
+pub fn shrink_arena(mut arenas: Query<(&mut Arena, &ShrinkSchedule)>, time: Res<Time>) {
+    arenas.iter_mut().for_each(|(mut arena, schedule)| {
+        let elapsed = time.elapsed_seconds() - arena.time_spawned;
+        let (current, next) = schedule.zones_at(elapsed);
+        let current_size = (arena.starting_size * current.scale).max(Vec2::splat(0.001));
+        let next_size = (arena.starting_size * next.scale).max(Vec2::splat(0.001));
+        // Avoid triggering change detection, and replication, when nothing moved
+        if arena.current_size != current_size
+            || arena.current_center != current.center
+            || arena.next_size != next_size
+            || arena.next_center != next.center
+        {
+            arena.current_size = current_size;
+            arena.current_center = current.center;
+            arena.next_size = next_size;
+            arena.next_center = next.center;
+        }
     });
 }
 
@@ -122,25 +417,166 @@ fn check_arena_residency(
         QueryFilter::default(),
         |ent| {
             let Ok(mut arena_resident) = arena_residents.get_mut(ent) else {
-        return true;
-      };
+                return true;
+            };
             arena_resident.is_outside = false;
             true
         },
     );
 }
 
+fn update_zone_warnings(
+    mut query: Query<(&ArenaResident, &mut ZoneWarning)>,
+    clock: Res<ServerClock>,
+    time: Res<Time>,
+) {
+    for (resident, mut warning) in query.iter_mut() {
+        // Only touch it when the player leaves or returns, clients count down on their own
+        if warning.is_outside != resident.is_outside {
+            *warning = ZoneWarning {
+                is_outside: resident.is_outside,
+                exited_at: clock.now(&time),
+            };
+        }
+    }
+}
+
 pub struct ArenaPlugin;
 
 impl Plugin for ArenaPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<Arena>();
         app.register_type::<Force>();
+        app.register_type::<ArenaShape>();
+        app.register_type::<ZoneWarning>();
         app.replicate::<Arena>();
-        app.add_systems((spawn_arena, shrink_arena, update_arena_size));
+        app.replicate::<ZoneWarning>();
+        app.replicate::<Force>();
+        app.add_systems((
+            spawn_arena,
+            shrink_arena.run_if(is_server()),
+            update_arena_size,
+        ));
 
         app.add_event::<ContactForceEvent>();
         app.add_event::<CollisionEvent>();
         app.add_system(check_arena_residency);
+        app.add_system(
+            update_zone_warnings
+                .after(check_arena_residency)
+                .run_if(is_server()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone(scale: f32, center: Vec2) -> Zone {
+        Zone { scale, center }
+    }
+
+    fn two_phases() -> ShrinkSchedule {
+        ShrinkSchedule {
+            phases: vec![
+                ShrinkPhase {
+                    pause: 10.0,
+                    duration: 20.0,
+                    scale: 0.5,
+                    center: Vec2::new(100.0, 0.0),
+                    easing: Easing::Linear,
+                },
+                ShrinkPhase {
+                    pause: 5.0,
+                    duration: 0.0,
+                    scale: 0.1,
+                    center: Vec2::ZERO,
+                    easing: Easing::Linear,
+                },
+            ],
+            min_scale: 0.2,
+        }
+    }
+
+    #[test]
+    fn holds_the_full_arena_during_the_first_pause() {
+        let schedule = two_phases();
+        assert_eq!(
+            schedule.zones_at(5.0),
+            (zone(1.0, Vec2::ZERO), zone(0.5, Vec2::new(100.0, 0.0)))
+        );
+    }
+
+    #[test]
+    fn shrinks_and_moves_during_a_phase() {
+        let schedule = two_phases();
+        assert_eq!(
+            schedule.zones_at(20.0),
+            (
+                zone(0.75, Vec2::new(50.0, 0.0)),
+                zone(0.5, Vec2::new(100.0, 0.0))
+            )
+        );
+    }
+
+    #[test]
+    fn jumps_to_zones_without_a_duration_and_respects_the_minimum_scale() {
+        let schedule = two_phases();
+        assert_eq!(
+            schedule.zones_at(32.0),
+            (zone(0.5, Vec2::new(100.0, 0.0)), zone(0.2, Vec2::ZERO))
+        );
+        assert_eq!(
+            schedule.zones_at(1000.0),
+            (zone(0.2, Vec2::ZERO), zone(0.2, Vec2::ZERO))
+        );
+    }
+
+    #[test]
+    fn zone_damage_starts_after_the_grace_period_and_escalates() {
+        let warning = ZoneWarning {
+            is_outside: true,
+            exited_at: 100.0,
+        };
+        assert_eq!(
+            warning.seconds_until_damage(101.0),
+            ZONE_GRACE_SECONDS - 1.0
+        );
+        assert_eq!(warning.damage_per_second(101.0), 0.0);
+        let damage_start = 100.0 + ZONE_GRACE_SECONDS;
+        assert_eq!(warning.damage_per_second(damage_start), ZONE_BASE_DAMAGE);
+        assert_eq!(
+            warning.damage_per_second(damage_start + 1.0),
+            ZONE_BASE_DAMAGE + ZONE_DAMAGE_INCREASE
+        );
+        assert_eq!(
+            warning.damage_per_second(damage_start + 1000.0),
+            ZONE_MAX_DAMAGE
+        );
+    }
+
+    #[test]
+    fn no_zone_damage_inside_the_arena() {
+        let warning = ZoneWarning {
+            is_outside: false,
+            exited_at: 0.0,
+        };
+        assert_eq!(warning.seconds_outside(1000.0), 0.0);
+        assert_eq!(warning.damage_per_second(1000.0), 0.0);
+    }
+
+    #[test]
+    fn easing_keeps_the_ends_in_place() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+        }
     }
 }
@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::{Path, ShapeBundle, Stroke};
 use bevy_rapier2d::prelude::{Collider, ColliderMassProperties, MassProperties};
+use bevy_replicon::prelude::RenetClient;
 use serde::{Deserialize, Serialize};
 
 use crate::bundles::{
@@ -11,8 +12,14 @@ use crate::bundles::{
     },
     PhysicsBundle,
 };
+use crate::game_manager::GameState;
 use crate::health::Health;
+use crate::level::Level;
+use crate::map::{resolve_map, MapDefinition, Maps, SelectedMap};
+use crate::network::handshake::ConnectionError;
 
+/// The shape of an asteroid.
+/// Not replicated, clients build it from the [`Level`] seed and the asteroid's [`LevelAsteroid`].
 #[derive(Component, Reflect, Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[reflect(Component, Default)]
 pub struct Asteroid {
@@ -29,6 +36,11 @@ impl Default for Asteroid {
     }
 }
 
+/// Which of the asteroids generated for the current [`Level`] this is.
+#[derive(Component, Reflect, Default, Copy, Clone, Debug)]
+#[reflect(Component, Default)]
+pub struct LevelAsteroid(pub u32);
+
 #[derive(Bundle)]
 pub struct AsteroidBundle {
     physics: PhysicsBundle,
@@ -74,6 +86,71 @@ impl From<(Transform, Path)> for AsteroidBundle {
     }
 }
 
+/// Gives replicated asteroids their [`Asteroid`] shape, built from the seed of the level.
+/// Retries every frame until the [`Level`] has been replicated too.
+/// Clients whose copy of the map differs from the server's would build asteroids that don't
+/// match, so they leave the game instead.
+pub fn shape_level_asteroids(
+    mut cmds: Commands,
+    new_roids: Query<(Entity, &LevelAsteroid), Without<Asteroid>>,
+    levels: Query<&Level>,
+    maps: Option<Res<Maps>>,
+    map_definitions: Option<Res<Assets<MapDefinition>>>,
+    mut generated: Local<Option<(Level, Vec<Asteroid>)>>,
+    connection_error: Option<Res<ConnectionError>>,
+    client: Option<ResMut<RenetClient>>,
+    game_state: Res<State<GameState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if new_roids.is_empty() || connection_error.is_some() {
+        return;
+    }
+    let Ok(level) = levels.get_single() else {
+        return;
+    };
+    if generated
+        .as_ref()
+        .map_or(true, |(generated_for, _)| generated_for != level)
+    {
+        let map = resolve_map(
+            &SelectedMap(level.map.clone()),
+            maps.as_deref(),
+            map_definitions.as_deref(),
+        );
+        if map.content_hash() != level.map_hash {
+            warn!("Local copy of map {} differs from the server's", level.map);
+            cmds.insert_resource(ConnectionError(format!(
+                "Your copy of the map {} is different from the server's.",
+                level.map
+            )));
+            if let Some(mut client) = client {
+                client.disconnect();
+            }
+            if game_state.0 != GameState::PreGame {
+                next_game_state.set(GameState::PreGame);
+            }
+            return;
+        }
+        let asteroids = map
+            .asteroids(&mut level.rng())
+            .into_iter()
+            .map(|(_, scale, path)| Asteroid { scale, path })
+            .collect();
+        *generated = Some((level.clone(), asteroids));
+    }
+    let Some((_, asteroids)) = generated.as_ref() else {
+        return;
+    };
+    for (entity, index) in new_roids.iter() {
+        match asteroids.get(index.0 as usize) {
+            Some(asteroid) => {
+                cmds.entity(entity).insert(*asteroid);
+            }
+            None => warn!("Level {} has no asteroid {}", level.map, index.0),
+        }
+    }
+}
+
 //TODO: Consider how to group functions like this
 pub fn asteroid_spawn(
     new_roids: Query<(Entity, &Asteroid, &Transform), Added<Asteroid>>,
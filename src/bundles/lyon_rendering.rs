@@ -2,6 +2,7 @@ use bevy::prelude::*;
 use bevy::sprite::Mesh2dHandle;
 use bevy_prototype_lyon::prelude::{Fill, Path, PathBuilder, ShapeBundle, Stroke};
 use bevy_prototype_lyon::render::ShapeMaterial;
+use std::f32::consts::TAU;
 
 use self::ship_paths::SHIP_PATH;
 
@@ -72,6 +73,18 @@ pub fn get_path_from_verts(points: &[(f32, f32)], scale: Vec2) -> Path {
     path_builder.build()
 }
 
+/// How many line segments to draw circles with.
+const CIRCLE_SEGMENTS: usize = 64;
+
+pub fn get_circle_path(radius: f32) -> Path {
+    let mut path_builder = PathBuilder::new();
+    for i in 0..=CIRCLE_SEGMENTS {
+        let angle = i as f32 / CIRCLE_SEGMENTS as f32 * TAU;
+        path_builder.line_to(Vec2::from_angle(angle) * radius);
+    }
+    path_builder.build()
+}
+
 pub fn spawn_test_renders(mut commands: Commands) {
     commands.spawn(LyonRenderBundle {
         shape_render: ShapeBundle {
@@ -113,10 +126,6 @@ pub const UNIT_SQUARE_PATH: [(f32, f32); 5] = [
     (-0.5, 0.5),
 ];
 
-pub mod projectile_paths {
-    pub const LASER_PATH: [(f32, f32); 2] = [(0.0, 0.0), (0.0, 10.0)];
-}
-
 pub mod roid_paths {
     use bevy::reflect::Reflect;
     use serde::{Deserialize, Serialize};
@@ -173,6 +182,58 @@ pub mod powerups {
         (-0.5, 0.5),
         (0.5, -0.5),
     ];
+
+    pub const SHIELD_PATH: [(f32, f32); 7] = [
+        (0.0, 0.5),
+        (0.5, 0.3),
+        (0.4, -0.2),
+        (0.0, -0.5),
+        (-0.4, -0.2),
+        (-0.5, 0.3),
+        (0.0, 0.5),
+    ];
+
+    /// Two arrows pointing away from each other.
+    pub const HITHER_THITHER_PATH: [(f32, f32); 9] = [
+        (-0.3, 0.2),
+        (-0.5, 0.0),
+        (-0.3, -0.2),
+        (-0.5, 0.0),
+        (0.5, 0.0),
+        (0.3, 0.2),
+        (0.5, 0.0),
+        (0.3, -0.2),
+        (0.5, 0.0),
+    ];
+
+    /// Debuff icons are drawn smaller in the corner of the powerup they come with.
+    pub const SLOWED_PATH: [(f32, f32); 3] = [(-0.5, 0.5), (0.0, -0.5), (0.5, 0.5)];
+
+    pub const INACCURACY_PATH: [(f32, f32); 4] =
+        [(-0.5, -0.5), (0.0, 0.5), (0.0, -0.1), (0.5, 0.4)];
+
+    /// A flame.
+    pub const HEALTH_BURN_PATH: [(f32, f32); 7] = [
+        (0.0, -0.5),
+        (-0.4, -0.2),
+        (-0.2, 0.5),
+        (0.0, 0.1),
+        (0.2, 0.5),
+        (0.4, -0.2),
+        (0.0, -0.5),
+    ];
+
+    /// Two arrows chasing each other around.
+    pub const REVERSED_CONTROLS_PATH: [(f32, f32); 8] = [
+        (-0.5, 0.2),
+        (0.3, 0.2),
+        (0.1, 0.4),
+        (0.3, 0.2),
+        (-0.3, -0.2),
+        (0.5, -0.2),
+        (-0.3, -0.2),
+        (-0.1, -0.4),
+    ];
 }
 
 pub mod ship_parts {
@@ -185,4 +246,8 @@ pub mod ship_parts {
         (0.3, -0.3 + 0.5),
         (0.5, -0.5 + 0.5),
     ];
+
+    /// Points back toward the arena when the ship is outside it.
+    pub const ZONE_INDICATOR: [(f32, f32); 4] =
+        [(-0.5, -0.5), (0.0, 0.5), (0.5, -0.5), (-0.5, -0.5)];
 }
@@ -1,22 +1,30 @@
+use crate::arena::Force;
 use crate::game_manager::GameState;
-use crate::network::commands::NetworkCommandsExt;
+use crate::game_mode::GameModeKind;
+use crate::level::LevelSeed;
+use crate::network::commands::{Listen, NetworkCommandsExt};
+use crate::network::interpolation::{InterpolationSettings, DEFAULT_INTERPOLATION_DELAY_MILLIS};
 use crate::network::DEFAULT_PORT;
+use crate::respawn::RespawnSettings;
+use crate::scoreboard::MatchLength;
+use crate::spectator::JoinPolicy;
+use crate::teams::TeamSettings;
 use bevy::prelude::*;
 use clap::Parser;
 use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
 
 pub struct CliPlugin;
 
 impl Plugin for CliPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Cli::parse());
         app.add_systems((cli_system.run_if(run_once()),).in_schedule(OnEnter(GameState::MainMenu)));
     }
 }
 
 #[derive(Debug, Parser, PartialEq, Resource)]
 #[command(author, version, about, long_about = None)]
-struct Cli {
+pub struct Cli {
     #[arg(short, long, default_value_t = DEFAULT_PORT)]
     port: u16,
     /// Usually you can leave this to be the default.
@@ -37,20 +45,122 @@ struct Cli {
     #[arg(short, long)]
     connect: Option<IpAddr>,
 
+    /// When connecting, watch the game without a ship.
+    #[arg(long, requires = "connect")]
+    spectate: bool,
+
     /// When creating a listen server, this sets the name of the server.
     #[arg(short, long)]
     name: Option<String>,
+
+    /// When creating a listen server, only let players with a join code connect.
+    /// Dedicated servers print join codes to the log.
+    #[arg(long, requires = "listen")]
+    secure: bool,
+
+    /// When creating a listen server, players without a join code need this password to join.
+    #[arg(long, requires = "listen")]
+    password: Option<String>,
+
+    /// When creating a listen server, the name of the map to play.
+    /// Maps are loaded from `assets/maps`.
+    #[arg(short, long)]
+    map: Option<String>,
+
+    /// Switch to the next of these maps after every round, e.g. `--map-rotation Classic,Corridor`.
+    #[arg(long, value_delimiter = ',')]
+    map_rotation: Vec<String>,
+
+    /// When creating a listen server, how the round is won.
+    #[arg(long, value_enum, default_value_t = GameModeKind::LastManStanding)]
+    mode: GameModeKind,
+
+    /// How many ships each player gets per round, 1 to play without respawning.
+    #[arg(long, default_value_t = 1)]
+    lives: u32,
+
+    /// Seconds before a destroyed ship respawns, when playing with more than one life.
+    #[arg(long, default_value_t = RespawnSettings::default().delay_seconds)]
+    respawn_delay: f32,
+
+    /// What happens to players that join in the middle of a round.
+    #[arg(long, value_enum, default_value_t = JoinPolicy::NextRound)]
+    join_policy: JoinPolicy,
+
+    /// Play matches of this many rounds, whoever wins the most rounds wins the match.
+    #[arg(long, conflicts_with = "first_to")]
+    rounds: Option<u32>,
+
+    /// Play matches until someone wins this many rounds. Defaults to 3.
+    #[arg(long)]
+    first_to: Option<u32>,
+
+    /// Split players into this many teams, leave out to play free for all.
+    #[arg(long, default_value_t = 0)]
+    teams: usize,
+
+    /// Let lasers hurt players on the same team.
+    #[arg(long)]
+    friendly_fire: bool,
+
+    /// Players on this team take less damage outside the arena, e.g. `--favored-team blue`.
+    #[arg(long, default_value_t = Force::None)]
+    favored_team: Force,
+
+    /// Run as a dedicated server with no window, renderer or local player.
+    /// Requires `--listen`.
+    #[arg(long, requires = "listen")]
+    pub dedicated: bool,
+
+    /// How many milliseconds behind the server other ships and asteroids are shown.
+    /// Higher values hide more lag and packet loss, but what you see is further in the past.
+    #[arg(long, default_value_t = DEFAULT_INTERPOLATION_DELAY_MILLIS)]
+    interpolation_delay: u64,
+
+    /// Build every level from this seed instead of a random one.
+    /// The seed of the current level is printed in the log, use this to reproduce it.
+    #[arg(long)]
+    seed: Option<u64>,
 }
 
-fn cli_system(mut commands: Commands, settings: Res<Cli>) {
+fn cli_system(
+    mut commands: Commands,
+    settings: Res<Cli>,
+    mut interpolation: ResMut<InterpolationSettings>,
+) {
+    interpolation.delay = Duration::from_millis(settings.interpolation_delay);
+    if let Some(seed) = settings.seed {
+        commands.insert_resource(LevelSeed::fixed(seed));
+    }
     if let Some(host_on_ip) = settings.listen {
-        commands.listen(
-            host_on_ip,
-            settings.bind,
-            settings.port,
-            settings.name.clone().unwrap_or("My Game".to_string()),
-        );
+        commands.listen(Listen {
+            ip: host_on_ip,
+            bind: settings.bind,
+            port: settings.port,
+            server_name: settings.name.clone().unwrap_or("My Game".to_string()),
+            secure: settings.secure,
+            password: settings.password.clone(),
+            map: settings.map.clone(),
+            map_rotation: settings.map_rotation.clone(),
+            teams: TeamSettings {
+                team_count: settings.teams,
+                friendly_fire: settings.friendly_fire,
+                favored_force: settings.favored_team,
+            },
+            game_mode: settings.mode,
+            match_length: match settings.rounds {
+                Some(rounds) => MatchLength::Rounds(rounds.max(1)),
+                None => settings.first_to.map_or_else(MatchLength::default, |wins| {
+                    MatchLength::FirstTo(wins.max(1))
+                }),
+            },
+            join_policy: settings.join_policy,
+            respawn: RespawnSettings {
+                lives: settings.lives.max(1),
+                delay_seconds: settings.respawn_delay.max(0.0),
+            },
+        });
     } else if let Some(join_ip) = settings.connect {
-        commands.connect(join_ip, settings.bind, settings.port);
+        commands.connect(join_ip, settings.bind, settings.port, settings.spectate);
     }
 }
@@ -0,0 +1,125 @@
+use crate::arena::Force;
+use crate::asteroid::Asteroid;
+use crate::game_manager::GameState;
+use crate::health::DamageSource;
+use crate::network::{is_server, NetworkOwner};
+use crate::player::weapons::DamagedEvent;
+use crate::player::Player;
+use crate::teams::TeamSettings;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_rapier2d::prelude::ContactForceEvent;
+
+/// Two bodies have to stop pushing on each other for this long before they can hurt each other
+/// again, so an impact that lasts several physics steps only counts once.
+const IMPACT_COOLDOWN_SECONDS: f32 = 0.5;
+
+pub struct CollisionDamagePlugin;
+
+impl Plugin for CollisionDamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<CollisionDamage>();
+        app.init_resource::<CollisionDamage>();
+        app.add_system(
+            damage_on_impact
+                .run_if(is_server())
+                .in_set(OnUpdate(GameState::Playing)),
+        );
+    }
+}
+
+/// How hard ships have to hit something before it hurts.
+///
+/// Forces are the total contact force rapier reports for one physics step. A ship flying into an
+/// asteroid at full speed hits with about 60.
+#[derive(Resource, Reflect, Debug, Copy, Clone)]
+#[reflect(Resource)]
+pub struct CollisionDamage {
+    /// Impacts softer than this do no damage, so ships can bump into things.
+    /// Also the threshold for rapier to report contact forces on ships.
+    pub min_force: f32,
+    /// Damage for every unit of force above [`CollisionDamage::min_force`].
+    pub damage_per_force: f32,
+    /// The most damage a single impact can do.
+    pub max_damage: f32,
+}
+
+impl Default for CollisionDamage {
+    fn default() -> Self {
+        Self {
+            min_force: 30.0,
+            damage_per_force: 0.5,
+            max_damage: 60.0,
+        }
+    }
+}
+
+impl CollisionDamage {
+    pub fn damage(&self, force: f32) -> f32 {
+        ((force - self.min_force) * self.damage_per_force).clamp(0.0, self.max_damage)
+    }
+}
+
+/// Hurts both bodies when a ship hits an asteroid or another ship hard enough.
+/// Only the strongest force of an impact counts, see [`IMPACT_COOLDOWN_SECONDS`].
+fn damage_on_impact(
+    mut contact_events: EventReader<ContactForceEvent>,
+    collision_damage: Res<CollisionDamage>,
+    players: Query<(&NetworkOwner, Option<&Force>), With<Player>>,
+    asteroids: Query<(), With<Asteroid>>,
+    team_settings: Res<TeamSettings>,
+    mut damaged_events: EventWriter<DamagedEvent>,
+    mut last_contact: Local<HashMap<(Entity, Entity), f32>>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    // Several physics steps can run in one frame, keep the hardest push of each pair
+    let mut impacts = HashMap::<(Entity, Entity), f32>::default();
+    for event in contact_events.iter() {
+        let pair = if event.collider1 < event.collider2 {
+            (event.collider1, event.collider2)
+        } else {
+            (event.collider2, event.collider1)
+        };
+        let force = impacts.entry(pair).or_default();
+        *force = force.max(event.total_force_magnitude);
+    }
+    last_contact.retain(|_, contact_time| now - *contact_time < IMPACT_COOLDOWN_SECONDS);
+
+    for ((collider1, collider2), force) in impacts {
+        // Still the same impact as an earlier frame, or the bodies are resting against each other
+        if last_contact.insert((collider1, collider2), now).is_some() {
+            continue;
+        }
+        let amount = collision_damage.damage(force);
+        if amount <= 0.0 {
+            continue;
+        }
+        // Asteroids bumping into each other don't hurt
+        if !players.contains(collider1) && !players.contains(collider2) {
+            continue;
+        }
+        for (entity, other) in [(collider1, collider2), (collider2, collider1)] {
+            // Whatever was hit takes the blame for the damage
+            let source = if let Ok((owner, other_team)) = players.get(other) {
+                let team = players.get(entity).ok().and_then(|(_, team)| team);
+                if !team_settings.can_damage(other_team, team) {
+                    continue;
+                }
+                DamageSource::Player(*owner)
+            } else if asteroids.contains(other) {
+                DamageSource::Asteroid
+            } else {
+                continue;
+            };
+            damaged_events.send(DamagedEvent {
+                entity,
+                amount,
+                source,
+                normal: None,
+                direction: None,
+                point: None,
+            });
+        }
+    }
+}
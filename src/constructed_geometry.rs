@@ -2,7 +2,9 @@ use bevy::prelude::*;
 use rand::Rng;
 
 pub trait PointDistribution {
-    fn point(&self) -> Vec2;
+    /// Picks a random point using `rng`.
+    /// Pass a seeded rng to get the same points every time.
+    fn point<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec2;
 }
 
 pub struct CircleShape {
@@ -11,9 +13,7 @@ pub struct CircleShape {
 }
 
 impl PointDistribution for CircleShape {
-    fn point(&self) -> Vec2 {
-        let mut rng = rand::thread_rng();
-
+    fn point<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec2 {
         let theta: f32 = rng.gen_range(0. ..6.29);
         let (sin, cos) = theta.sin_cos();
 
@@ -38,9 +38,7 @@ pub struct QuadShape {
 }
 
 impl PointDistribution for QuadShape {
-    fn point(&self) -> Vec2 {
-        let mut rng = rand::thread_rng();
-
+    fn point<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec2 {
         Vec2 {
             x: rng.gen_range(0. ..self.width),
             y: rng.gen_range(0. ..self.height),
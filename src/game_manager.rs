@@ -1,22 +1,24 @@
-use std::f32::consts::PI;
-
 use bevy::prelude::*;
 use bevy::render::view::NoFrustumCulling;
 use bevy_replicon::prelude::{SendMode, ServerEventAppExt, ToClients};
 use bevy_replicon::renet::RenetServer;
-use bevy_replicon::replication_core::Replication;
+use bevy_replicon::replication_core::{AppReplicationExt, Replication};
 use bevy_replicon::server::SERVER_ID;
-use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-use crate::network::{is_server, NetworkOwner};
+use crate::game_mode::RoundStatus;
+use crate::level::{log_level_seed, Level, LevelSeed};
+use crate::map::{advance_map_rotation, resolve_map, MapDefinition, Maps, SelectedMap};
+use crate::network::{has_window, is_server, DedicatedServer, NetworkOwner};
 use crate::player::commands::PlayerCommands;
-use crate::player::{Player, PlayerColor, Players};
-use crate::powerup::{spawn_powerup, Debuff, PowerUp};
+use crate::player::{PlayerColor, Players};
+use crate::powerup::{pick_powerup, spawn_powerup, LEVEL_POWERUPS};
+use crate::scoreboard::Scoreboard;
+use crate::spectator::Spectators;
+use crate::teams::TeamSettings;
 use crate::{
     arena::{Arena, Force},
-    asteroid::{asteroid_spawn, Asteroid},
-    bundles::lyon_rendering::roid_paths::RoidPath,
+    asteroid::{asteroid_spawn, shape_level_asteroids, Asteroid, LevelAsteroid},
 };
 
 #[derive(Debug, Hash, Eq, PartialEq, Clone, States, Default, Reflect)]
@@ -30,9 +32,10 @@ pub enum GameState {
     PostGame,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Reflect, Serialize, Deserialize)]
 pub enum GameEvent {
     RoundWon { winner: PlayerColor },
+    TeamWon { team: Force },
     Tie,
     Restart,
 }
@@ -44,14 +47,28 @@ impl Plugin for GameManager {
         app.add_server_event::<GameEvent>();
         app.register_type::<RestartCountdown>();
         app.register_type::<PostGameUiRoot>();
+        app.register_type::<ScoreboardTable>();
+        app.register_type::<Level>();
+        app.register_type::<LevelSeed>();
+        app.init_resource::<LevelSeed>();
+        app.register_type::<TeamSettings>();
+        app.init_resource::<TeamSettings>();
+        app.replicate::<Level>();
+        app.add_system(log_level_seed);
         app.add_systems((load_state,).in_schedule(OnEnter(GameState::Loading)));
+        app.add_system(show_post_game_text.run_if(has_window()));
+        app.add_system(update_scoreboard_table.run_if(has_window()));
+        app.add_system(update_restart_countdown);
         app.add_system(
-            end_game_last_man_standing
+            start_restart_timer
                 .run_if(is_server())
-                .in_set(OnUpdate(GameState::Playing)),
+                .in_schedule(OnEnter(GameState::PostGame)),
+        );
+        app.add_system(
+            restart_after_delay
+                .run_if(is_server())
+                .in_set(OnUpdate(GameState::PostGame)),
         );
-        app.add_system(show_post_game_text);
-        app.add_system(update_restart_countdown);
         app.add_systems(
             (build_level.run_if(is_server()),).in_schedule(OnEnter(GameState::Playing)),
         );
@@ -59,25 +76,35 @@ impl Plugin for GameManager {
         app.add_systems(
             (
                 despawn_everything.run_if(is_server()),
+                // Before players spawn, so they use the spawn points of the next map
+                advance_map_rotation.run_if(is_server()),
                 reload_with_current_players.run_if(is_server()),
+                advance_level_seed.run_if(is_server()),
             )
                 .chain()
                 .in_schedule(OnExit(GameState::PostGame)),
         );
 
-        app.add_system(
-            return_to_pregame_if_no_clients
-                .run_if(is_server())
-                .in_set(OnUpdate(GameState::PostGame)),
-        );
-
-        app.add_systems((asteroid_spawn,));
+        app.add_systems((shape_level_asteroids, asteroid_spawn).chain());
     }
 }
 
 #[derive(Component, Reflect, Default)]
 pub struct Persist;
 
+/// How many players need to be connected before a round will start.
+pub const MIN_PLAYERS: usize = 2;
+
+/// The number of players taking part in the game.
+/// This counts the host's own player unless we are a dedicated server, and leaves out spectators.
+pub fn connected_player_count(
+    server: &RenetServer,
+    dedicated: bool,
+    spectators: &Spectators,
+) -> usize {
+    (server.clients_id().len() + usize::from(!dedicated)).saturating_sub(spectators.count())
+}
+
 #[derive(Component, Reflect, Default)]
 pub struct RestartCountdown {
     restart_at_time: f64,
@@ -98,89 +125,77 @@ pub fn load_state(mut next_state: ResMut<NextState<GameState>>) {
 }
 
 ///Should only be run by the server, and then fill backfill on the clients
-pub fn build_level(mut cmds: Commands, time: Res<Time>) {
-    let arena_size = Vec2::new(1800.0, 900.0);
+pub fn build_level(
+    mut cmds: Commands,
+    time: Res<Time>,
+    level_seed: Res<LevelSeed>,
+    selected_map: Res<SelectedMap>,
+    maps: Option<Res<Maps>>,
+    map_definitions: Option<Res<Assets<MapDefinition>>>,
+    team_settings: Res<TeamSettings>,
+) {
+    let map = resolve_map(&selected_map, maps.as_deref(), map_definitions.as_deref());
+    info!("Building {} with seed {}", map.name, level_seed.seed);
+    let mut rng = level_seed.rng();
+    // Clients build the asteroids themselves from the seed, so they have to come first
+    let asteroids = map.asteroids(&mut rng);
+
     cmds.spawn(Arena {
-        starting_size: arena_size,
-        current_size: arena_size,
+        shape: map.arena.shape,
+        starting_size: map.arena.size,
+        current_size: map.arena.size,
+        current_center: Vec2::ZERO,
+        next_size: map.arena.size,
+        next_center: Vec2::ZERO,
         time_spawned: time.elapsed_seconds(),
-        friendly_force: Force::None,
+        friendly_force: team_settings.favored_force,
     })
+    .insert(map.arena.shrink.clone())
     .insert(Name::new("Arena"))
+    .insert(Level {
+        seed: level_seed.seed,
+        map: map.name.clone(),
+        map_hash: map.content_hash(),
+    })
+    .insert(RoundStatus::default())
     .insert(Replication::default());
 
-    let mut rng = rand::thread_rng();
-
-    for _ in 0..rng.gen_range(1..5) {
-        let x = rng.gen_range(-850.0..850.0);
-        let y = rng.gen_range(-400.0..400.0);
-
-        //Current these aren't really explicitly checked, intent was to make them composable.
-        let powerup_type = match rng.gen_range(0..2) {
-            0 => (PowerUp::RapidFire, Debuff::Slowed),
-            1 => (PowerUp::Scattergun, Debuff::Inaccuracy),
-            _ => (PowerUp::Scattergun, Debuff::Inaccuracy), //TODO: Rethink this, currently it's hardcoded and this isn't really used to compose
+    for position in map.powerup_positions(&mut rng) {
+        let Some((powerup, debuff)) = pick_powerup(&LEVEL_POWERUPS, &mut rng) else {
+            break;
         };
         spawn_powerup(
             &mut cmds,
-            Transform::from_xyz(x, y, 0.2),
-            powerup_type.0,
-            powerup_type.1,
+            Transform::from_translation(position.extend(0.2)),
+            powerup,
+            debuff,
         );
     }
 
-    for _ in 0..rng.gen_range(30..45) {
-        let roid_path = match (rng.gen_range(0..10) % 2) == 0 {
-            true => RoidPath::One,
-            false => RoidPath::Two,
-        };
-
-        let x = rng.gen_range(-850.0..850.0);
-        let y = rng.gen_range(-400.0..400.0);
-        let scale = rng.gen_range(16.0..64.0);
-        let rotation = rng.gen_range(0.0..(PI * 2.0));
-
-        let mut transform = Transform::from_xyz(x, y, 0.2);
-        transform.rotate_z(rotation);
+    for (index, (transform, scale, roid_path)) in asteroids.into_iter().enumerate() {
         cmds.spawn((
             Asteroid {
                 scale,
                 path: roid_path,
             },
+            LevelAsteroid(index as u32),
             transform,
             Replication::default(),
         ));
     }
 }
 
-pub fn end_game_last_man_standing(
-    query: Query<&Player, With<Player>>,
-    mut game_state: ResMut<NextState<GameState>>,
-    mut game_events: EventWriter<ToClients<GameEvent>>,
-) {
-    if query.iter().count() <= 1 {
-        if let Ok(player) = query.get_single() {
-            game_events.send(ToClients {
-                mode: SendMode::Broadcast,
-                event: GameEvent::RoundWon {
-                    winner: player.color,
-                },
-            });
-        } else {
-            game_events.send(ToClients {
-                mode: SendMode::Broadcast,
-                event: GameEvent::Tie,
-            });
-        }
-
-        info!("Last Player Standing!");
-        game_state.set(GameState::PostGame);
-    }
+fn advance_level_seed(mut level_seed: ResMut<LevelSeed>) {
+    level_seed.advance();
 }
 
 #[derive(Component, Debug, Default, Reflect)]
 pub struct PostGameUiRoot;
 
+/// Filled with the [`Scoreboard`] of the match between rounds.
+#[derive(Component, Debug, Default, Reflect)]
+pub struct ScoreboardTable;
+
 fn show_post_game_text(
     mut commands: Commands,
     asset_server: ResMut<AssetServer>,
@@ -192,6 +207,7 @@ fn show_post_game_text(
         info!("GameEvent from server {event:?}");
         let win_text = match event {
             GameEvent::RoundWon { winner } => (format!("{} wins!\n\n", winner), winner.color()),
+            GameEvent::TeamWon { team } => (format!("{} team wins!\n\n", team), team.color()),
             GameEvent::Tie => ("Tie!".to_string(), Color::YELLOW),
             GameEvent::Restart => {
                 ui_root_query.for_each(|e| {
@@ -251,34 +267,133 @@ fn show_post_game_text(
                         ..default()
                     },
                 ));
+                child_builder.spawn((
+                    ScoreboardTable,
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ));
             });
     }
 }
 
-fn update_restart_countdown(
-    mut query: Query<(&mut Text, &RestartCountdown)>,
-    time: Res<Time>,
-    mut game_state: ResMut<NextState<GameState>>,
-    server: Option<Res<RenetServer>>,
-    mut game_events: EventWriter<ToClients<GameEvent>>,
+fn update_scoreboard_table(
+    mut commands: Commands,
+    scoreboard: Res<Scoreboard>,
+    tables: Query<Entity, With<ScoreboardTable>>,
+    new_tables: Query<(), Added<ScoreboardTable>>,
+    asset_server: Res<AssetServer>,
 ) {
+    if !scoreboard.is_changed() && new_tables.is_empty() {
+        return;
+    }
+    let font = asset_server.load("hyperspace_font/Hyperspace Bold.otf");
+    let cell = |text: String, width: f32, color: Color| TextBundle {
+        text: Text::from_section(
+            text,
+            TextStyle {
+                font: font.clone(),
+                font_size: 20.0,
+                color,
+            },
+        ),
+        style: Style {
+            size: Size::width(Val::Px(width)),
+            ..default()
+        },
+        ..default()
+    };
+    for table in tables.iter() {
+        commands
+            .entity(table)
+            .despawn_descendants()
+            .with_children(|child_builder| {
+                child_builder.spawn(cell(scoreboard.summary(), 480.0, Color::YELLOW));
+                let header = ["", "Wins", "Kills", "Deaths", "Damage"].map(str::to_string);
+                let rows = scoreboard.ranking().into_iter().map(|(color, score)| {
+                    (
+                        color.color(),
+                        [
+                            color.to_string(),
+                            score.round_wins.to_string(),
+                            score.kills.to_string(),
+                            score.deaths.to_string(),
+                            format!("{:.0}", score.damage_dealt),
+                        ],
+                    )
+                });
+                for (color, columns) in std::iter::once((Color::YELLOW, header)).chain(rows) {
+                    child_builder
+                        .spawn(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Row,
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|row| {
+                            for column in columns {
+                                row.spawn(cell(column, 96.0, color));
+                            }
+                        });
+                }
+            });
+    }
+}
+
+fn update_restart_countdown(mut query: Query<(&mut Text, &RestartCountdown)>, time: Res<Time>) {
     for (mut text, countdown) in query.iter_mut() {
         let time_remaining = countdown.restart_at_time - time.elapsed_seconds_f64();
         text.sections[0].value = if time_remaining > 0.0 {
             format!("Restarting in {:.0}", time_remaining.ceil())
         } else {
-            if server.is_some() {
-                game_state.set(GameState::Playing);
-                game_events.send(ToClients {
-                    mode: SendMode::Broadcast,
-                    event: GameEvent::Restart,
-                })
-            }
             "Restarting".to_string()
         };
     }
 }
 
+/// Server side timer that starts the next round.
+/// This is separate from [`RestartCountdown`] so dedicated servers without UI can still restart.
+#[derive(Resource)]
+pub struct RestartTimer(Timer);
+
+fn start_restart_timer(mut commands: Commands) {
+    commands.insert_resource(RestartTimer(Timer::from_seconds(
+        RestartCountdown::DEFAULT_RESTART_DELAY_SECONDS as f32,
+        TimerMode::Once,
+    )));
+}
+
+fn restart_after_delay(
+    mut timer: ResMut<RestartTimer>,
+    time: Res<Time>,
+    server: Res<RenetServer>,
+    dedicated: Option<Res<DedicatedServer>>,
+    spectators: Res<Spectators>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut game_events: EventWriter<ToClients<GameEvent>>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    if connected_player_count(&server, dedicated.is_some(), &spectators) < MIN_PLAYERS {
+        warn!("Not enough players connected, returning to pregame");
+        next_game_state.set(GameState::PreGame);
+    } else {
+        next_game_state.set(GameState::Playing);
+    }
+    game_events.send(ToClients {
+        mode: SendMode::Broadcast,
+        event: GameEvent::Restart,
+    });
+}
+
 #[cfg(feature = "bevy_editor_pls")]
 type PersistentRootEntities = (
     Without<Parent>,
@@ -305,24 +420,14 @@ fn reload_with_current_players(
     mut commands: Commands,
     server: Res<RenetServer>,
     player_colors: Res<Players>,
+    dedicated: Option<Res<DedicatedServer>>,
 ) {
-    commands.spawn_player(PlayerColor::Red, NetworkOwner(SERVER_ID));
+    if dedicated.is_none() {
+        commands.spawn_player(PlayerColor::Red, NetworkOwner(SERVER_ID));
+    }
     for client_id in server.clients_id() {
         if let Some(color) = player_colors.color(client_id) {
             commands.spawn_player(color, NetworkOwner(client_id));
         }
     }
 }
-
-fn return_to_pregame_if_no_clients(
-    server: Res<RenetServer>,
-    mut next_game_state: ResMut<NextState<GameState>>,
-    mut removed: RemovedComponents<RestartCountdown>,
-) {
-    let countdown_finished = removed.iter().count() > 0;
-
-    if countdown_finished && server.clients_id().is_empty() {
-        warn!("No clients connected, returning to pregame");
-        next_game_state.set(GameState::PreGame);
-    }
-}
@@ -0,0 +1,525 @@
+use crate::arena::{Arena, Force};
+use crate::game_manager::{GameEvent, GameState};
+use crate::health::{update_health_on_damage, DeathEvent};
+use crate::map::{MapDefinition, PlayerSpawn};
+use crate::network::{is_server, NetworkOwner};
+use crate::player::{Player, PlayerColor, Players};
+use crate::respawn::{queue_respawns, Respawns};
+use crate::scoreboard::{RoundParticipants, Scoreboard};
+use crate::teams::TeamSettings;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use bevy_replicon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How long a round of timed deathmatch lasts.
+pub const DEATHMATCH_ROUND_SECONDS: f32 = 180.0;
+/// Radius of the hill in the center of the arena in king of the hill.
+/// It gets smaller with the arena once the arena can't fit it.
+pub const HILL_RADIUS: f32 = 100.0;
+/// Seconds a side has to hold the hill to win.
+pub const HILL_TARGET_SECONDS: f32 = 30.0;
+
+pub struct GameModePlugin;
+
+impl Plugin for GameModePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<RoundStatus>();
+        app.replicate::<RoundStatus>();
+        app.init_resource::<ActiveGameMode>();
+        app.add_system(
+            start_round
+                .run_if(is_server())
+                .in_schedule(OnEnter(GameState::Playing)),
+        );
+        app.add_system(
+            run_game_mode
+                // While the ship that died still exists, so we know whose it was
+                .after(update_health_on_damage)
+                // And once we know if it is coming back
+                .after(queue_respawns)
+                .run_if(is_server())
+                .in_set(OnUpdate(GameState::Playing)),
+        );
+    }
+}
+
+/// The game modes hosts can pick from.
+#[derive(clap::ValueEnum, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum GameModeKind {
+    #[default]
+    LastManStanding,
+    TimedDeathmatch,
+    KingOfTheHill,
+}
+
+impl GameModeKind {
+    pub const ALL: [GameModeKind; 3] = [
+        GameModeKind::LastManStanding,
+        GameModeKind::TimedDeathmatch,
+        GameModeKind::KingOfTheHill,
+    ];
+
+    pub fn create(&self) -> Box<dyn GameMode> {
+        match self {
+            GameModeKind::LastManStanding => Box::<LastManStanding>::default(),
+            GameModeKind::TimedDeathmatch => Box::<TimedDeathmatch>::default(),
+            GameModeKind::KingOfTheHill => Box::<KingOfTheHill>::default(),
+        }
+    }
+}
+
+impl std::fmt::Display for GameModeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameModeKind::LastManStanding => write!(f, "Last Man Standing"),
+            GameModeKind::TimedDeathmatch => write!(f, "Timed Deathmatch"),
+            GameModeKind::KingOfTheHill => write!(f, "King of the Hill"),
+        }
+    }
+}
+
+/// Who is competing for the win, players without a team are on a side of their own.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+pub enum Side {
+    Team(Force),
+    Solo(PlayerColor),
+}
+
+impl Side {
+    pub fn of(color: PlayerColor, team: Option<&Force>) -> Self {
+        match team {
+            Some(team) => Side::Team(*team),
+            None => Side::Solo(color),
+        }
+    }
+
+    /// The event announcing this side won the round.
+    pub fn won(&self) -> GameEvent {
+        match self {
+            Side::Team(team) => GameEvent::TeamWon { team: *team },
+            Side::Solo(winner) => GameEvent::RoundWon { winner: *winner },
+        }
+    }
+}
+
+impl std::fmt::Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Side::Team(team) => write!(f, "{team} team"),
+            Side::Solo(color) => write!(f, "{color}"),
+        }
+    }
+}
+
+/// What a game mode gets to look at every frame.
+pub struct RoundContext<'a> {
+    /// The side and position of every player still alive.
+    pub players: &'a [(Side, Vec2)],
+    /// The side of every player waiting to respawn, they are still in the round.
+    pub respawning: &'a [Side],
+    pub arena: Option<&'a Arena>,
+    /// Seconds since the round started.
+    pub round_time: f32,
+    pub delta_seconds: f32,
+}
+
+impl RoundContext<'_> {
+    pub fn sides_alive(&self) -> HashSet<Side> {
+        self.players
+            .iter()
+            .map(|(side, _)| *side)
+            .chain(self.respawning.iter().copied())
+            .collect()
+    }
+
+    /// The last side standing, or a tie if nobody is left.
+    /// `None` while more than one side is still alive.
+    pub fn last_side_standing(&self) -> Option<GameEvent> {
+        let sides = self.sides_alive();
+        if sides.len() > 1 {
+            return None;
+        }
+        Some(
+            sides
+                .into_iter()
+                .next()
+                .map_or(GameEvent::Tie, |side| side.won()),
+        )
+    }
+}
+
+/// Rules for a round: where players spawn, how they score and when someone has won.
+/// Only runs on the server, clients see the state of the round through [`RoundStatus`].
+pub trait GameMode: Send + Sync + 'static {
+    fn kind(&self) -> GameModeKind;
+
+    /// Reset any scores from the previous round.
+    fn start_round(&mut self);
+
+    fn player_spawn(&self, map: &MapDefinition, player_index: usize) -> PlayerSpawn {
+        map.player_spawn(player_index)
+    }
+
+    /// Called when `killer` destroys a ship on the `victim` side.
+    fn on_kill(&mut self, _killer: Side, _victim: Side) {}
+
+    /// Called every frame while playing, returns how the round ended once it is over.
+    fn update(&mut self, context: &RoundContext) -> Option<GameEvent>;
+
+    /// Shown to players during the round.
+    fn status(&self, context: &RoundContext) -> RoundStatus;
+}
+
+/// The game mode the server is playing.
+#[derive(Resource)]
+pub struct ActiveGameMode(pub Box<dyn GameMode>);
+
+impl Default for ActiveGameMode {
+    fn default() -> Self {
+        Self(GameModeKind::default().create())
+    }
+}
+
+/// Replicated on the arena so clients can show how the round is going.
+#[derive(Component, Reflect, Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[reflect(Component, Default)]
+pub struct RoundStatus {
+    pub summary: String,
+    /// The radius of the hill around the arena's center, only set in king of the hill.
+    pub hill_radius: Option<f32>,
+}
+
+/// The round ends when only one side has ships left.
+#[derive(Default)]
+pub struct LastManStanding;
+
+impl GameMode for LastManStanding {
+    fn kind(&self) -> GameModeKind {
+        GameModeKind::LastManStanding
+    }
+
+    fn start_round(&mut self) {}
+
+    fn update(&mut self, context: &RoundContext) -> Option<GameEvent> {
+        context.last_side_standing()
+    }
+
+    fn status(&self, context: &RoundContext) -> RoundStatus {
+        RoundStatus {
+            summary: format!("{} left", context.sides_alive().len()),
+            hill_radius: None,
+        }
+    }
+}
+
+/// The side with the most kills when time runs out, or when everyone else is gone, wins.
+#[derive(Default)]
+pub struct TimedDeathmatch {
+    kills: HashMap<Side, u32>,
+}
+
+impl TimedDeathmatch {
+    fn leader(&self) -> Option<GameEvent> {
+        let most_kills = self.kills.values().copied().max()?;
+        let mut leaders = self.kills.iter().filter(|(_, kills)| **kills == most_kills);
+        match (leaders.next(), leaders.next()) {
+            (Some((side, _)), None) => Some(side.won()),
+            _ => None,
+        }
+    }
+}
+
+impl GameMode for TimedDeathmatch {
+    fn kind(&self) -> GameModeKind {
+        GameModeKind::TimedDeathmatch
+    }
+
+    fn start_round(&mut self) {
+        self.kills.clear();
+    }
+
+    fn on_kill(&mut self, killer: Side, victim: Side) {
+        if killer != victim {
+            *self.kills.entry(killer).or_default() += 1;
+        }
+    }
+
+    fn update(&mut self, context: &RoundContext) -> Option<GameEvent> {
+        if context.round_time < DEATHMATCH_ROUND_SECONDS && context.sides_alive().len() > 1 {
+            return None;
+        }
+        Some(self.leader().unwrap_or(GameEvent::Tie))
+    }
+
+    fn status(&self, context: &RoundContext) -> RoundStatus {
+        let time_left = (DEATHMATCH_ROUND_SECONDS - context.round_time)
+            .max(0.0)
+            .ceil() as u32;
+        let mut kills = self
+            .kills
+            .iter()
+            .map(|(side, kills)| (side.to_string(), *kills))
+            .collect::<Vec<_>>();
+        kills.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let mut summary = format!("{}:{:02}", time_left / 60, time_left % 60);
+        for (side, kills) in kills {
+            summary.push_str(&format!("\n{side}: {kills}"));
+        }
+        RoundStatus {
+            summary,
+            hill_radius: None,
+        }
+    }
+}
+
+/// Hold the center of the arena on your own to score, first to [`HILL_TARGET_SECONDS`] wins.
+#[derive(Default)]
+pub struct KingOfTheHill {
+    time_on_hill: HashMap<Side, f32>,
+}
+
+impl KingOfTheHill {
+    fn hill(arena: &Arena) -> (Vec2, f32) {
+        let radius = HILL_RADIUS.min(arena.current_size.min_element() / 2.0);
+        (arena.current_center, radius)
+    }
+}
+
+impl GameMode for KingOfTheHill {
+    fn kind(&self) -> GameModeKind {
+        GameModeKind::KingOfTheHill
+    }
+
+    fn start_round(&mut self) {
+        self.time_on_hill.clear();
+    }
+
+    fn update(&mut self, context: &RoundContext) -> Option<GameEvent> {
+        if let Some(outcome) = context.last_side_standing() {
+            return Some(outcome);
+        }
+        let (center, radius) = Self::hill(context.arena?);
+        let on_hill = context
+            .players
+            .iter()
+            .filter(|(_, position)| position.distance(center) <= radius)
+            .map(|(side, _)| *side)
+            .collect::<HashSet<_>>();
+        // Contested hills don't score for anyone
+        if on_hill.len() != 1 {
+            return None;
+        }
+        let king = on_hill.into_iter().next()?;
+        let time = self.time_on_hill.entry(king).or_default();
+        *time += context.delta_seconds;
+        (*time >= HILL_TARGET_SECONDS).then(|| king.won())
+    }
+
+    fn status(&self, context: &RoundContext) -> RoundStatus {
+        let mut times = self.time_on_hill.iter().collect::<Vec<_>>();
+        times.sort_by(|a, b| b.1.total_cmp(a.1));
+        let mut summary = format!("Hold the hill for {HILL_TARGET_SECONDS:.0}s");
+        for (side, time) in times {
+            summary.push_str(&format!("\n{side}: {:.0}", time.floor()));
+        }
+        RoundStatus {
+            summary,
+            hill_radius: context.arena.map(|arena| Self::hill(arena).1),
+        }
+    }
+}
+
+fn start_round(mut mode: ResMut<ActiveGameMode>) {
+    info!("Starting a round of {}", mode.0.kind());
+    mode.0.start_round();
+}
+
+fn run_game_mode(
+    mut mode: ResMut<ActiveGameMode>,
+    players: Query<(&Player, Option<&Force>, &Transform)>,
+    ships: Query<(&NetworkOwner, Option<&Force>), With<Player>>,
+    mut arenas: Query<(&Arena, &mut RoundStatus)>,
+    mut death_events: EventReader<DeathEvent>,
+    player_colors: Res<Players>,
+    team_settings: Res<TeamSettings>,
+    respawns: Res<Respawns>,
+    mut scoreboard: ResMut<Scoreboard>,
+    participants: Res<RoundParticipants>,
+    time: Res<Time>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut game_events: EventWriter<ToClients<GameEvent>>,
+) {
+    // A ship only counts as one kill, even if something sends its death twice
+    let mut destroyed = HashSet::new();
+    for event in death_events.iter() {
+        let Ok((victim, victim_team, _)) = players.get(event.entity) else {
+            continue;
+        };
+        if !destroyed.insert(event.entity) {
+            continue;
+        }
+        let Some(attacker) = event.source.attacker() else {
+            continue;
+        };
+        let Some(killer) = player_colors.color(attacker.0) else {
+            continue;
+        };
+        // Use the team of the killer's ship like every other side, the team settings only
+        // decide it for ships that are already gone
+        let killer_team = match ships.iter().find(|(owner, _)| **owner == attacker) {
+            Some((_, team)) => team.copied(),
+            None => team_settings.team_for(killer as usize),
+        };
+        mode.0.on_kill(
+            Side::of(killer, killer_team.as_ref()),
+            Side::of(victim.color, victim_team),
+        );
+    }
+
+    let alive = players
+        .iter()
+        .map(|(player, team, transform)| (Side::of(player.color, team), transform.translation.xy()))
+        .collect::<Vec<_>>();
+    let respawning = respawns
+        .pending()
+        .map(|color| Side::of(color, team_settings.team_for(color as usize).as_ref()))
+        .collect::<Vec<_>>();
+    let mut arena = arenas.get_single_mut().ok();
+    let context = RoundContext {
+        players: &alive,
+        respawning: &respawning,
+        arena: arena.as_ref().map(|(arena, _)| *arena),
+        round_time: arena.as_ref().map_or(0.0, |(arena, _)| {
+            time.elapsed_seconds() - arena.time_spawned
+        }),
+        delta_seconds: time.delta_seconds(),
+    };
+
+    let outcome = mode.0.update(&context);
+    let new_status = mode.0.status(&context);
+    if let Some((_, status)) = arena.as_mut() {
+        if **status != new_status {
+            **status = new_status;
+        }
+    }
+
+    if let Some(event) = outcome {
+        info!("{} round over: {event:?}", mode.0.kind());
+        scoreboard.record_round(&event, &participants, &team_settings);
+        game_events.send(ToClients {
+            mode: SendMode::Broadcast,
+            event,
+        });
+        game_state.set(GameState::PostGame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::ArenaShape;
+    use crate::health::{DamageSource, Health, HealthLostEvent};
+    use crate::network::clock::ServerClock;
+    use crate::player::weapons::DamagedEvent;
+
+    /// Runs timed deathmatch with Red (client 1) shooting at Blue (client 2).
+    fn deathmatch_app() -> App {
+        let mut app = App::new();
+        app.add_event::<DamagedEvent>();
+        app.add_event::<DeathEvent>();
+        app.add_event::<HealthLostEvent>();
+        app.add_event::<ToClients<GameEvent>>();
+        app.init_resource::<ServerClock>();
+        app.init_resource::<Time>();
+        app.init_resource::<NextState<GameState>>();
+        app.init_resource::<TeamSettings>();
+        app.init_resource::<Respawns>();
+        app.init_resource::<Scoreboard>();
+        app.init_resource::<RoundParticipants>();
+        app.insert_resource(ActiveGameMode(GameModeKind::TimedDeathmatch.create()));
+        let mut players = Players::default();
+        players.insert(PlayerColor::Red, 1);
+        players.insert(PlayerColor::Blue, 2);
+        app.insert_resource(players);
+        app.add_systems((update_health_on_damage, run_game_mode).chain());
+        app
+    }
+
+    fn spawn_ship(app: &mut App, color: PlayerColor, owner: u64) -> Entity {
+        let player = Player { color, ..default() };
+        app.world
+            .spawn((
+                player,
+                NetworkOwner(owner),
+                Health::default(),
+                Transform::default(),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn several_lethal_hits_in_one_frame_score_one_kill() {
+        let mut app = deathmatch_app();
+        let size = Vec2::splat(1000.0);
+        let arena = app
+            .world
+            .spawn((
+                Arena {
+                    shape: ArenaShape::Rectangle,
+                    starting_size: size,
+                    current_size: size,
+                    current_center: Vec2::ZERO,
+                    next_size: size,
+                    next_center: Vec2::ZERO,
+                    time_spawned: 0.0,
+                    friendly_force: Force::None,
+                },
+                RoundStatus::default(),
+            ))
+            .id();
+        spawn_ship(&mut app, PlayerColor::Red, 1);
+        let blue = spawn_ship(&mut app, PlayerColor::Blue, 2);
+        for _ in 0..2 {
+            app.world.send_event(DamagedEvent {
+                entity: blue,
+                amount: 150.0,
+                source: DamageSource::Player(NetworkOwner(1)),
+                normal: None,
+                direction: None,
+                point: None,
+            });
+        }
+        app.update();
+
+        let status = app.world.get::<RoundStatus>(arena).unwrap();
+        assert_eq!(status.summary, "3:00\nRed: 1");
+    }
+
+    #[test]
+    fn repeated_deaths_of_one_ship_score_one_kill() {
+        let mut mode = TimedDeathmatch::default();
+        let mut app = deathmatch_app();
+        spawn_ship(&mut app, PlayerColor::Red, 1);
+        let blue = spawn_ship(&mut app, PlayerColor::Blue, 2);
+        for _ in 0..2 {
+            app.world.send_event(DeathEvent {
+                entity: blue,
+                source: DamageSource::Player(NetworkOwner(1)),
+            });
+        }
+        app.update();
+
+        mode.on_kill(Side::Solo(PlayerColor::Red), Side::Solo(PlayerColor::Blue));
+        let context = RoundContext {
+            players: &[],
+            respawning: &[],
+            arena: None,
+            round_time: 0.0,
+            delta_seconds: 0.0,
+        };
+        let active = app.world.resource::<ActiveGameMode>();
+        assert_eq!(active.0.status(&context), mode.status(&context));
+    }
+}
@@ -1,9 +1,14 @@
 use crate::asteroid::Asteroid;
+use crate::network::clock::ServerClock;
+use crate::network::NetworkOwner;
 use crate::player::weapons::DamagedEvent;
-use crate::player::Player;
+use crate::player::{Player, PlayerColor, Players};
+use crate::powerup::Shield;
+use crate::respawn::Invulnerable;
 use bevy::prelude::*;
-use bevy_replicon::prelude::AppReplicationExt;
+use bevy_replicon::prelude::*;
 use bevy_replicon::server::ServerSet;
+use serde::{Deserialize, Serialize};
 
 pub struct HealthPlugin;
 
@@ -11,10 +16,17 @@ impl Plugin for HealthPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<Health>();
         app.add_event::<DeathEvent>();
+        app.add_event::<HealthLostEvent>();
+        app.add_server_event::<KillEvent>();
         // Replicating health so the client can show UI related to their health.
         app.replicate::<Health>();
         app.add_system(update_health_on_damage.in_set(ServerSet::Authority));
         app.add_system(despawn_on_death::<Asteroid>.in_set(ServerSet::Authority));
+        app.add_system(
+            announce_kills
+                .after(update_health_on_damage)
+                .in_set(ServerSet::Authority),
+        );
         app.add_system(despawn_on_death::<Player>.in_set(ServerSet::Authority));
     }
 }
@@ -29,6 +41,55 @@ pub struct Health {
 #[derive(Debug)]
 pub struct DeathEvent {
     pub entity: Entity,
+    /// What did the final blow.
+    pub source: DamageSource,
+}
+
+/// Sent for the damage that actually came off an entity's health, after shields and
+/// invulnerability.
+#[derive(Debug)]
+pub struct HealthLostEvent {
+    pub entity: Entity,
+    pub amount: f32,
+    pub source: DamageSource,
+}
+
+/// What damage came from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DamageSource {
+    /// Shot or rammed by a player.
+    Player(NetworkOwner),
+    /// Staying outside the arena.
+    Arena,
+    /// Crashing into an asteroid.
+    Asteroid,
+    /// The [`Debuff::HealthBurn`](crate::powerup::Debuff::HealthBurn) debuff.
+    HealthBurn,
+}
+
+impl DamageSource {
+    /// The player responsible for the damage, if any.
+    pub fn attacker(&self) -> Option<NetworkOwner> {
+        match self {
+            DamageSource::Player(owner) => Some(*owner),
+            _ => None,
+        }
+    }
+}
+
+/// Sent to clients when a player's ship is destroyed so they can show it in the kill feed.
+#[derive(Debug, Reflect, Serialize, Deserialize)]
+pub struct KillEvent {
+    pub victim: PlayerColor,
+    pub cause: KillCause,
+}
+
+#[derive(Debug, Copy, Clone, Reflect, FromReflect, Serialize, Deserialize)]
+pub enum KillCause {
+    Player(PlayerColor),
+    Arena,
+    Asteroid,
+    HealthBurn,
 }
 
 impl Default for Health {
@@ -40,18 +101,45 @@ impl Default for Health {
     }
 }
 
-fn update_health_on_damage(
-    mut query: Query<&mut Health>,
+pub fn update_health_on_damage(
+    mut query: Query<(&mut Health, Option<&Invulnerable>, Option<&mut Shield>)>,
     mut damage_events: EventReader<DamagedEvent>,
     mut death_events: EventWriter<DeathEvent>,
+    mut health_lost_events: EventWriter<HealthLostEvent>,
+    clock: Res<ServerClock>,
+    time: Res<Time>,
 ) {
+    let now = clock.now(&time);
     for event in damage_events.iter() {
-        if let Ok(mut health) = query.get_mut(event.entity) {
-            health.current -= event.amount;
+        if let Ok((mut health, invulnerable, shield)) = query.get_mut(event.entity) {
+            if invulnerable.map_or(false, |invulnerable| invulnerable.is_active(now)) {
+                continue;
+            }
+            // Burning comes from inside the ship, so the shield doesn't help
+            let amount = match shield {
+                Some(mut shield)
+                    if shield.strength > 0.0 && event.source != DamageSource::HealthBurn =>
+                {
+                    shield.absorb(event.amount)
+                }
+                _ => event.amount,
+            };
+            // Only the hit that takes the ship from alive to dead kills it, later hits in the same
+            // frame land before the despawn and mustn't kill it again
+            let before = health.current;
+            health.current -= amount;
             health.current = health.current.max(0.0);
-            if health.current <= 0.0 {
+            if health.current < before {
+                health_lost_events.send(HealthLostEvent {
+                    entity: event.entity,
+                    amount: before - health.current,
+                    source: event.source,
+                });
+            }
+            if before > 0.0 && health.current <= 0.0 {
                 death_events.send(DeathEvent {
                     entity: event.entity,
+                    source: event.source,
                 });
             }
         }
@@ -71,3 +159,67 @@ fn despawn_on_death<C: Component>(
         }
     }
 }
+
+fn announce_kills(
+    mut death_events: EventReader<DeathEvent>,
+    players: Query<&Player>,
+    player_colors: Res<Players>,
+    mut kill_events: EventWriter<ToClients<KillEvent>>,
+) {
+    for event in death_events.iter() {
+        let Ok(victim) = players.get(event.entity) else {
+            continue;
+        };
+        let cause = match event.source {
+            DamageSource::Player(owner) => match player_colors.color(owner.0) {
+                Some(killer) => KillCause::Player(killer),
+                None => continue,
+            },
+            DamageSource::Arena => KillCause::Arena,
+            DamageSource::Asteroid => KillCause::Asteroid,
+            DamageSource::HealthBurn => KillCause::HealthBurn,
+        };
+        kill_events.send(ToClients {
+            mode: SendMode::Broadcast,
+            event: KillEvent {
+                victim: victim.color,
+                cause,
+            },
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn damage(entity: Entity, amount: f32) -> DamagedEvent {
+        DamagedEvent {
+            entity,
+            amount,
+            source: DamageSource::Arena,
+            normal: None,
+            direction: None,
+            point: None,
+        }
+    }
+
+    #[test]
+    fn several_lethal_hits_in_one_frame_kill_once() {
+        let mut app = App::new();
+        app.add_event::<DamagedEvent>();
+        app.add_event::<DeathEvent>();
+        app.add_event::<HealthLostEvent>();
+        app.init_resource::<ServerClock>();
+        app.init_resource::<Time>();
+        app.add_system(update_health_on_damage);
+        let ship = app.world.spawn(Health::default()).id();
+
+        app.world.send_event(damage(ship, 150.0));
+        app.world.send_event(damage(ship, 150.0));
+        app.update();
+
+        assert_eq!(app.world.resource::<Events<DeathEvent>>().len(), 1);
+        assert_eq!(app.world.get::<Health>(ship).unwrap().current, 0.0);
+    }
+}
@@ -0,0 +1,70 @@
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// The rng used for level generation.
+/// Unlike [`rand::rngs::StdRng`], ChaCha8 is guaranteed to produce the same values for a seed on
+/// every platform and version, so a level can always be rebuilt from its seed.
+pub type LevelRng = ChaCha8Rng;
+
+/// Drives everything random about building a level.
+/// Only used on the server, clients get the seed of the current level from [`Level`].
+#[derive(Resource, Reflect, Debug, Clone, Copy)]
+#[reflect(Resource)]
+pub struct LevelSeed {
+    pub seed: u64,
+    /// Keep using this seed every round instead of picking a new one, set with `--seed`.
+    pub fixed: bool,
+}
+
+impl Default for LevelSeed {
+    fn default() -> Self {
+        Self {
+            seed: rand::random(),
+            fixed: false,
+        }
+    }
+}
+
+impl LevelSeed {
+    pub fn fixed(seed: u64) -> Self {
+        Self { seed, fixed: true }
+    }
+
+    pub fn rng(&self) -> LevelRng {
+        LevelRng::seed_from_u64(self.seed)
+    }
+
+    /// Picks the seed for the next round.
+    pub fn advance(&mut self) {
+        if !self.fixed {
+            self.seed = rand::random();
+        }
+    }
+}
+
+/// Replicated on the arena so clients know which map and seed the current level was built from,
+/// and can build the same asteroids from it.
+#[derive(Component, Reflect, Default, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[reflect(Component, Default)]
+pub struct Level {
+    pub seed: u64,
+    pub map: String,
+    /// The [`MapDefinition::content_hash`](crate::map::MapDefinition::content_hash) of the map,
+    /// clients with a different copy of it can't build the same level.
+    pub map_hash: u64,
+}
+
+impl Level {
+    /// The rng the level was built with, the asteroids are generated first.
+    pub fn rng(&self) -> LevelRng {
+        LevelSeed::fixed(self.seed).rng()
+    }
+}
+
+pub fn log_level_seed(query: Query<&Level, Added<Level>>) {
+    for level in query.iter() {
+        info!("Playing {} with seed {}", level.map, level.seed);
+    }
+}
@@ -6,75 +6,135 @@
 mod cli;
 pub mod powerup;
 
-use crate::cli::CliPlugin;
+use crate::cli::{Cli, CliPlugin};
 use crate::game_manager::{GameState, Persist};
 use crate::health::HealthPlugin;
-use crate::network::NetworkPlugin;
+use crate::map::MapPlugin;
+use crate::network::{DedicatedServer, NetworkPlugin};
 use crate::player::PlayerPlugin;
 use arena::ArenaPlugin;
 use audio::SqueezeAudioPlugin;
+use bevy::app::ScheduleRunnerSettings;
 use bevy::core_pipeline::bloom::{BloomCompositeMode, BloomSettings};
 use bevy::core_pipeline::clear_color::ClearColorConfig;
 use bevy::core_pipeline::tonemapping::{DebandDither, Tonemapping};
+use bevy::hierarchy::HierarchyPlugin;
+use bevy::input::InputPlugin;
+use bevy::log::LogPlugin;
 use bevy::prelude::*;
 use bevy::render::camera::ScalingMode;
+use bevy::transform::TransformPlugin;
 use bevy_egui::EguiPlugin;
 use bevy_mod_reqwest::ReqwestPlugin;
 use bevy_prototype_lyon::prelude::ShapePlugin;
 use bevy_rapier2d::prelude::{DebugRenderContext, NoUserData, RapierPhysicsPlugin};
 use bevy_rapier2d::render::RapierDebugRenderPlugin;
+use clap::Parser;
+use collision::CollisionDamagePlugin;
 use game_manager::GameManager;
+use game_mode::GameModePlugin;
 use powerup::PowerupPlugin;
+use respawn::RespawnPlugin;
+use scoreboard::ScoreboardPlugin;
+use spectator::SpectatorPlugin;
+use std::time::Duration;
 
 mod arena;
 mod asteroid;
 mod audio;
 mod bundles;
+mod collision;
 mod constructed_geometry;
 mod game_manager;
+mod game_mode;
 mod health;
+mod level;
+mod map;
 mod network;
 mod player;
+mod respawn;
+mod scoreboard;
+mod spectator;
+mod teams;
 mod ui;
 
+/// How many times per second the dedicated server runs its schedule.
+const DEDICATED_TICK_RATE: f64 = 60.0;
+
 fn main() {
+    let cli = Cli::parse();
+    let dedicated = cli.dedicated;
+
     let mut app = App::new();
-    app.add_plugins(DefaultPlugins);
+    app.insert_resource(cli);
+
+    if dedicated {
+        // No window, renderer or audio, so this can run on a machine without a GPU.
+        app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(
+            1.0 / DEDICATED_TICK_RATE,
+        )))
+        .add_plugins(MinimalPlugins)
+        .add_plugin(LogPlugin::default())
+        .add_plugin(AssetPlugin {
+            watch_for_changes: true,
+            ..default()
+        })
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        .add_plugin(InputPlugin)
+        .insert_resource(DedicatedServer);
+    } else {
+        // Watch for changes so maps can be edited while the game is running
+        app.add_plugins(DefaultPlugins.set(AssetPlugin {
+            watch_for_changes: true,
+            ..default()
+        }));
 
-    #[cfg(feature = "bevy_editor_pls")]
-    {
-        use bevy_editor_pls::EditorPlugin;
-        app.add_plugin(EditorPlugin::default());
+        #[cfg(feature = "bevy_editor_pls")]
+        {
+            use bevy_editor_pls::EditorPlugin;
+            app.add_plugin(EditorPlugin::default());
+        }
     }
 
     app.add_state::<GameState>()
+        .add_plugin(MapPlugin)
         .add_plugin(NetworkPlugin)
         .add_plugin(PlayerPlugin)
-        .add_plugin(ShapePlugin)
         .add_plugin(ReqwestPlugin)
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
-        .add_plugin(RapierDebugRenderPlugin {
-            enabled: false,
-            ..default()
-        })
         .add_plugin(GameManager)
+        .add_plugin(GameModePlugin)
+        .add_plugin(ScoreboardPlugin)
+        .add_plugin(SpectatorPlugin)
+        .add_plugin(RespawnPlugin)
+        .add_plugin(CollisionDamagePlugin)
         .add_plugin(ArenaPlugin)
         .add_plugin(CliPlugin)
         .add_plugin(HealthPlugin)
-        .add_plugin(bevy_kira_audio::AudioPlugin)
-        .add_plugin(SqueezeAudioPlugin)
-        .add_plugin(ui::UiPlugin)
-        .add_plugin(PowerupPlugin)
-        .insert_resource(Msaa::Sample8);
+        .add_plugin(PowerupPlugin);
 
     app.register_type::<MainCamera>();
 
-    if !app.is_plugin_added::<EguiPlugin>() {
-        app.add_plugin(EguiPlugin);
+    if !dedicated {
+        app.add_plugin(ShapePlugin)
+            .add_plugin(RapierDebugRenderPlugin {
+                enabled: false,
+                ..default()
+            })
+            .add_plugin(bevy_kira_audio::AudioPlugin)
+            .add_plugin(SqueezeAudioPlugin)
+            .add_plugin(ui::UiPlugin)
+            .insert_resource(Msaa::Sample8);
+
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugin(EguiPlugin);
+        }
+
+        app.add_startup_system(setup);
+        app.add_system(debug_rapier);
     }
 
-    app.add_startup_system(setup);
-    app.add_system(debug_rapier);
     app.run();
 }
 
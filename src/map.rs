@@ -0,0 +1,458 @@
+use crate::arena::{ArenaShape, ShrinkSchedule};
+use crate::bundles::lyon_rendering::roid_paths::RoidPath;
+use crate::constructed_geometry::{PointDistribution, QuadShape};
+use crate::network::protocol::Fnv1a;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::Deserialize;
+use std::ops::Range;
+
+/// The map used when hosting without picking one, or if the selected map failed to load.
+pub const DEFAULT_MAP: &str = "Classic";
+
+/// Loads the map files in `assets/maps`.
+///
+/// Maps are `.map.ron` files describing a [`MapDefinition`].
+/// Changes to them are picked up while the game is running, and apply from the next round.
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<MapDefinition>();
+        app.init_asset_loader::<MapLoader>();
+        app.init_resource::<SelectedMap>();
+        app.add_startup_system(load_maps);
+        app.add_system(log_map_changes);
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "5f6d8c53-0a43-4d1e-9a6a-35c1f3e8b7a2"]
+pub struct MapDefinition {
+    /// Shown when picking a map, and used to select it.
+    pub name: String,
+    pub arena: ArenaDefinition,
+    pub asteroids: AsteroidField,
+    pub powerups: PowerupSpawns,
+    /// Players are assigned these in order of their color.
+    pub player_spawns: Vec<PlayerSpawn>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArenaDefinition {
+    #[serde(default)]
+    pub shape: ArenaShape,
+    /// For circles, x is the diameter.
+    pub size: Vec2,
+    #[serde(default)]
+    pub shrink: ShrinkSchedule,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum AsteroidField {
+    /// Scatter asteroids randomly within an area centered on the arena.
+    Procedural {
+        count: Range<u32>,
+        scale: Range<f32>,
+        area: Vec2,
+    },
+    /// Place asteroids exactly where the map says.
+    Fixed(Vec<AsteroidPlacement>),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AsteroidPlacement {
+    pub position: Vec2,
+    /// In degrees.
+    pub rotation: f32,
+    pub scale: f32,
+    pub path: RoidPath,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum PowerupSpawns {
+    /// Spawn powerups at random positions within an area centered on the arena.
+    Procedural { count: Range<u32>, area: Vec2 },
+    /// Spawn powerups at a random selection of these points.
+    Points {
+        count: Range<u32>,
+        points: Vec<Vec2>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PlayerSpawn {
+    pub position: Vec2,
+    /// In degrees.
+    pub rotation: f32,
+}
+
+impl Default for MapDefinition {
+    /// Matches `assets/maps/classic.map.ron`, so there is still something to play if the map
+    /// files are missing.
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_MAP.to_string(),
+            arena: ArenaDefinition {
+                shape: ArenaShape::Rectangle,
+                size: Vec2::new(1800.0, 900.0),
+                shrink: ShrinkSchedule::default(),
+            },
+            asteroids: AsteroidField::Procedural {
+                count: 30..45,
+                scale: 16.0..64.0,
+                area: Vec2::new(1700.0, 800.0),
+            },
+            powerups: PowerupSpawns::Procedural {
+                count: 1..5,
+                area: Vec2::new(1700.0, 800.0),
+            },
+            player_spawns: vec![
+                PlayerSpawn {
+                    position: Vec2::new(440.0, 350.0),
+                    rotation: 135.0,
+                },
+                PlayerSpawn {
+                    position: Vec2::new(-440.0, -350.0),
+                    rotation: -45.0,
+                },
+                PlayerSpawn {
+                    position: Vec2::new(440.0, -350.0),
+                    rotation: 45.0,
+                },
+                PlayerSpawn {
+                    position: Vec2::new(-440.0, 350.0),
+                    rotation: -135.0,
+                },
+                PlayerSpawn {
+                    position: Vec2::new(0.0, 350.0),
+                    rotation: 180.0,
+                },
+                PlayerSpawn {
+                    position: Vec2::new(0.0, -350.0),
+                    rotation: 0.0,
+                },
+            ],
+        }
+    }
+}
+
+impl MapDefinition {
+    pub fn player_spawn(&self, index: usize) -> PlayerSpawn {
+        if self.player_spawns.is_empty() {
+            warn!("Map {} has no player spawns", self.name);
+            return PlayerSpawn {
+                position: Vec2::ZERO,
+                rotation: 0.0,
+            };
+        }
+        self.player_spawns[index % self.player_spawns.len()]
+    }
+
+    /// Identifies everything this map builds, so clients can tell when their copy of a map file
+    /// differs from the one the server built the level with.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        hasher.write(&format!("{self:?}"));
+        hasher.0
+    }
+
+    /// Where to put the asteroids for this round, as (transform, scale, path).
+    pub fn asteroids<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<(Transform, f32, RoidPath)> {
+        match &self.asteroids {
+            AsteroidField::Procedural { count, scale, area } => {
+                let count = random_count(rng, count);
+                (0..count)
+                    .map(|_| {
+                        let roid_path = match (rng.gen_range(0..10) % 2) == 0 {
+                            true => RoidPath::One,
+                            false => RoidPath::Two,
+                        };
+                        let position = random_point_in_area(rng, *area);
+                        let scale = if scale.is_empty() {
+                            scale.start
+                        } else {
+                            rng.gen_range(scale.clone())
+                        };
+                        let rotation = rng.gen_range(0.0..std::f32::consts::TAU);
+                        let mut transform = Transform::from_translation(position.extend(0.2));
+                        transform.rotate_z(rotation);
+                        (transform, scale, roid_path)
+                    })
+                    .collect()
+            }
+            AsteroidField::Fixed(placements) => placements
+                .iter()
+                .map(|placement| {
+                    let transform = Transform::from_translation(placement.position.extend(0.2))
+                        .with_rotation(Quat::from_rotation_z(placement.rotation.to_radians()));
+                    (transform, placement.scale, placement.path)
+                })
+                .collect(),
+        }
+    }
+
+    /// Where to put the powerups for this round.
+    pub fn powerup_positions<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<Vec2> {
+        match &self.powerups {
+            PowerupSpawns::Procedural { count, area } => {
+                let count = random_count(rng, count);
+                (0..count)
+                    .map(|_| random_point_in_area(rng, *area))
+                    .collect()
+            }
+            PowerupSpawns::Points { count, points } => {
+                let count = random_count(rng, count) as usize;
+                points.choose_multiple(rng, count).copied().collect()
+            }
+        }
+    }
+}
+
+fn random_count<R: Rng + ?Sized>(rng: &mut R, count: &Range<u32>) -> u32 {
+    if count.is_empty() {
+        count.start
+    } else {
+        rng.gen_range(count.clone())
+    }
+}
+
+fn random_point_in_area<R: Rng + ?Sized>(rng: &mut R, area: Vec2) -> Vec2 {
+    QuadShape {
+        width: area.x,
+        height: area.y,
+    }
+    .point(rng)
+        - area / 2.0
+}
+
+#[derive(Default)]
+pub struct MapLoader;
+
+impl AssetLoader for MapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let map = ron::de::from_bytes::<MapDefinition>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(map));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map.ron"]
+    }
+}
+
+/// Handles to every map in `assets/maps`.
+#[derive(Resource, Default)]
+pub struct Maps {
+    handles: Vec<Handle<MapDefinition>>,
+}
+
+impl Maps {
+    pub fn get<'a>(
+        &self,
+        assets: &'a Assets<MapDefinition>,
+        name: &str,
+    ) -> Option<&'a MapDefinition> {
+        self.handles
+            .iter()
+            .filter_map(|handle| assets.get(handle))
+            .find(|map| map.name == name)
+    }
+
+    /// The names of all maps that have finished loading, sorted alphabetically.
+    pub fn names(&self, assets: &Assets<MapDefinition>) -> Vec<String> {
+        let mut names = self
+            .handles
+            .iter()
+            .filter_map(|handle| assets.get(handle))
+            .map(|map| map.name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+}
+
+/// The map the server will build for the next round.
+#[derive(Resource, Debug, Clone)]
+pub struct SelectedMap(pub String);
+
+impl Default for SelectedMap {
+    fn default() -> Self {
+        Self(DEFAULT_MAP.to_string())
+    }
+}
+
+/// Maps to cycle through, switching to the next one after every round.
+/// Only exists on the server if the host configured a rotation.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct MapRotation {
+    pub maps: Vec<String>,
+}
+
+impl MapRotation {
+    /// The map after `current`, or the first map if `current` isn't in the rotation.
+    pub fn next(&self, current: &str) -> Option<&String> {
+        let next_index = self
+            .maps
+            .iter()
+            .position(|name| name == current)
+            .map_or(0, |index| index + 1);
+        self.maps.get(next_index % self.maps.len().max(1))
+    }
+}
+
+pub fn advance_map_rotation(
+    mut selected_map: ResMut<SelectedMap>,
+    rotation: Option<Res<MapRotation>>,
+) {
+    let Some(next) = rotation
+        .as_ref()
+        .and_then(|rotation| rotation.next(&selected_map.0))
+    else {
+        return;
+    };
+    info!("Next map in rotation: {next}");
+    selected_map.0 = next.clone();
+}
+
+/// The definition of the [`SelectedMap`], or the default map if it isn't loaded.
+pub fn selected_map_definition(world: &World) -> MapDefinition {
+    resolve_map(
+        world.resource::<SelectedMap>(),
+        world.get_resource::<Maps>(),
+        world.get_resource::<Assets<MapDefinition>>(),
+    )
+}
+
+pub fn resolve_map(
+    selected: &SelectedMap,
+    maps: Option<&Maps>,
+    assets: Option<&Assets<MapDefinition>>,
+) -> MapDefinition {
+    maps.zip(assets)
+        .and_then(|(maps, assets)| maps.get(assets, &selected.0))
+        .cloned()
+        .unwrap_or_else(|| {
+            warn!("Map {} is not loaded, using the default map", selected.0);
+            MapDefinition::default()
+        })
+}
+
+fn load_maps(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handles = match asset_server.load_folder("maps") {
+        Ok(handles) => handles
+            .into_iter()
+            .map(|handle| handle.typed::<MapDefinition>())
+            .collect(),
+        Err(e) => {
+            error!("Could not load maps: {e}");
+            Vec::new()
+        }
+    };
+    commands.insert_resource(Maps { handles });
+}
+
+fn log_map_changes(
+    mut events: EventReader<AssetEvent<MapDefinition>>,
+    assets: Res<Assets<MapDefinition>>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            if let Some(map) = assets.get(handle) {
+                info!("Reloaded map {}, changes will apply next round", map.name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::LevelSeed;
+
+    fn maps() -> Vec<MapDefinition> {
+        let mut points_map = MapDefinition::default();
+        points_map.powerups = PowerupSpawns::Points {
+            count: 1..3,
+            points: vec![Vec2::ZERO, Vec2::X, Vec2::Y, Vec2::NEG_X],
+        };
+        vec![MapDefinition::default(), points_map]
+    }
+
+    #[test]
+    fn same_seed_builds_the_same_level() {
+        for map in maps() {
+            for seed in [0, 1, 42, u64::MAX] {
+                let mut rng = LevelSeed::fixed(seed).rng();
+                let mut other_rng = LevelSeed::fixed(seed).rng();
+                assert_eq!(map.asteroids(&mut rng), map.asteroids(&mut other_rng));
+                assert_eq!(
+                    map.powerup_positions(&mut rng),
+                    map.powerup_positions(&mut other_rng)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn content_hash_changes_with_the_map() {
+        let map = MapDefinition::default();
+        assert_eq!(map.content_hash(), map.clone().content_hash());
+
+        let mut edited = map.clone();
+        edited.asteroids = AsteroidField::Procedural {
+            count: 30..46,
+            scale: 16.0..64.0,
+            area: Vec2::new(1700.0, 800.0),
+        };
+        assert_ne!(map.content_hash(), edited.content_hash());
+
+        let mut moved_spawn = map.clone();
+        moved_spawn.player_spawns[0].position.x += 1.0;
+        assert_ne!(map.content_hash(), moved_spawn.content_hash());
+    }
+
+    fn rotation(maps: &[&str]) -> MapRotation {
+        MapRotation {
+            maps: maps.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn rotation_moves_to_the_next_map_and_wraps_around() {
+        let rotation = rotation(&["Classic", "Corridor", "Drift"]);
+        assert_eq!(rotation.next("Classic").unwrap(), "Corridor");
+        assert_eq!(rotation.next("Corridor").unwrap(), "Drift");
+        assert_eq!(rotation.next("Drift").unwrap(), "Classic");
+    }
+
+    #[test]
+    fn rotation_starts_over_from_a_map_outside_of_it() {
+        let rotation = rotation(&["Corridor", "Drift"]);
+        assert_eq!(rotation.next("Classic").unwrap(), "Corridor");
+    }
+
+    #[test]
+    fn empty_rotation_has_no_next_map() {
+        assert_eq!(rotation(&[]).next("Classic"), None);
+    }
+
+    #[test]
+    fn different_seeds_build_different_asteroids() {
+        let map = MapDefinition::default();
+        assert_ne!(
+            map.asteroids(&mut LevelSeed::fixed(1).rng()),
+            map.asteroids(&mut LevelSeed::fixed(2).rng())
+        );
+    }
+}
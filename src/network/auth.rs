@@ -0,0 +1,183 @@
+use crate::network::protocol::protocol_id;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use bevy::prelude::*;
+use bevy_replicon::renet::{ConnectToken, ServerEvent, NETCODE_KEY_BYTES};
+use std::fmt::{Display, Formatter};
+use std::net::SocketAddr;
+use std::time::SystemTime;
+
+/// How long a join code can be used for after it is created.
+pub const JOIN_CODE_EXPIRE_SECONDS: u64 = 300;
+/// How long the connection can go without packets before the client is dropped.
+pub const CONNECTION_TIMEOUT_SECONDS: i32 = 15;
+
+/// Only exists on the host of a secure game.
+/// Holds the private key used to sign the connect tokens handed out as join codes.
+#[derive(Resource)]
+pub struct ConnectTokenIssuer {
+    private_key: [u8; NETCODE_KEY_BYTES],
+    public_addr: SocketAddr,
+}
+
+impl ConnectTokenIssuer {
+    pub fn new(public_addr: SocketAddr) -> Self {
+        Self {
+            private_key: rand::random(),
+            public_addr,
+        }
+    }
+
+    pub fn private_key(&self) -> [u8; NETCODE_KEY_BYTES] {
+        self.private_key
+    }
+
+    /// Creates a join code for a single client.
+    /// Every code gets a new random client id so ids can't collide or be picked by the client.
+    ///
+    /// The code is the connect token in URL safe base64. Most of the token is the part encrypted
+    /// for the server, which can't be made any smaller, so this is as short as a code that works
+    /// without asking the host for anything can be.
+    pub fn issue_join_code(&self) -> Result<JoinCode, JoinCodeError> {
+        let client_id = rand::random();
+        let current_time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        let connect_token = ConnectToken::generate(
+            current_time,
+            protocol_id(),
+            JOIN_CODE_EXPIRE_SECONDS,
+            client_id,
+            CONNECTION_TIMEOUT_SECONDS,
+            vec![self.public_addr],
+            None,
+            &self.private_key,
+        )
+        .map_err(|e| JoinCodeError(e.to_string()))?;
+
+        let mut bytes = Vec::new();
+        connect_token
+            .write(&mut bytes)
+            .map_err(|e| JoinCodeError(e.to_string()))?;
+        Ok(JoinCode {
+            client_id,
+            code: URL_SAFE_NO_PAD.encode(bytes),
+        })
+    }
+}
+
+/// A join code from [`ConnectTokenIssuer::issue_join_code`].
+#[derive(Debug, Clone)]
+pub struct JoinCode {
+    /// The client id the code lets connect, so the host knows when it was used.
+    pub client_id: u64,
+    pub code: String,
+}
+
+/// Dedicated servers have no window to hand out join codes from, so they print them to the log.
+/// A new code is printed when the client the last one was for connected, or when it expired.
+pub fn log_join_codes(
+    issuer: Res<ConnectTokenIssuer>,
+    time: Res<Time>,
+    mut server_events: EventReader<ServerEvent>,
+    mut issued_at: Local<Option<f32>>,
+    mut issued_to: Local<Option<u64>>,
+) {
+    let code_used = server_events.iter().any(|event| {
+        matches!(event, ServerEvent::ClientConnected(client_id, _) if Some(*client_id) == *issued_to)
+    });
+    let code_expired = issued_at.map_or(true, |issued_at| {
+        time.elapsed_seconds() - issued_at >= JOIN_CODE_EXPIRE_SECONDS as f32
+    });
+    if !issuer.is_added() && !code_used && !code_expired {
+        return;
+    }
+    match issuer.issue_join_code() {
+        Ok(join_code) => {
+            info!("Join code: {}", join_code.code);
+            *issued_to = Some(join_code.client_id);
+        }
+        Err(e) => error!("Could not create join code: {e}"),
+    }
+    *issued_at = Some(time.elapsed_seconds());
+}
+
+/// Turns a join code created with [`ConnectTokenIssuer::issue_join_code`] back into a token.
+pub fn decode_join_code(join_code: &str) -> Result<ConnectToken, JoinCodeError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(join_code.trim())
+        .map_err(|_| JoinCodeError("Join code is not valid".to_string()))?;
+
+    let connect_token =
+        ConnectToken::read(&mut bytes.as_slice()).map_err(|e| JoinCodeError(e.to_string()))?;
+    if connect_token.protocol_id != protocol_id() {
+        return Err(JoinCodeError(
+            "Version mismatch: this join code is for a different version of the game".to_string(),
+        ));
+    }
+    Ok(connect_token)
+}
+
+#[derive(Debug, Clone)]
+pub struct JoinCodeError(String);
+
+impl Display for JoinCodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for JoinCodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn issuer() -> ConnectTokenIssuer {
+        ConnectTokenIssuer::new(SocketAddr::new(
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            4761,
+        ))
+    }
+
+    #[test]
+    fn issued_join_codes_decode() {
+        let join_code = issuer().issue_join_code().unwrap();
+        let connect_token = decode_join_code(&join_code.code).unwrap();
+        assert_eq!(connect_token.protocol_id, protocol_id());
+    }
+
+    #[test]
+    fn join_codes_fit_in_a_chat_message() {
+        let join_code = issuer().issue_join_code().unwrap();
+        assert!(join_code.code.len() < 2000, "{}", join_code.code.len());
+        assert!(join_code
+            .code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    }
+
+    #[test]
+    fn every_join_code_is_for_a_new_client() {
+        let issuer = issuer();
+        let first = issuer.issue_join_code().unwrap();
+        let second = issuer.issue_join_code().unwrap();
+        assert_ne!(first.client_id, second.client_id);
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_ignored() {
+        let join_code = issuer().issue_join_code().unwrap();
+        assert!(decode_join_code(&format!("  {}\n", join_code.code)).is_ok());
+    }
+
+    #[test]
+    fn broken_join_codes_are_rejected() {
+        let join_code = issuer().issue_join_code().unwrap().code;
+        assert!(decode_join_code(&join_code[1..]).is_err());
+        assert!(decode_join_code(&join_code[..join_code.len() / 2]).is_err());
+        assert!(decode_join_code(&"!".repeat(join_code.len())).is_err());
+        assert!(decode_join_code("").is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use crate::network::{is_client, is_server};
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use bevy_replicon::renet::ServerEvent;
+use serde::{Deserialize, Serialize};
+
+/// How often the server tells clients what time it is.
+const CLOCK_SYNC_INTERVAL_SECONDS: f32 = 1.0;
+
+/// Lets replicated components hold timestamps instead of values that change every frame.
+///
+/// The server sends its elapsed time to clients now and then, and clients keep the offset to their
+/// own. Timestamps taken with [`ServerClock::now`] then mean the same thing everywhere, so the
+/// client can count down on its own and only needs an update when the timestamp changes.
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ServerClock>();
+        app.add_server_event::<ClockSync>();
+        app.add_system(send_clock_sync.run_if(is_server()));
+        app.add_system(receive_clock_sync.run_if(is_client()));
+    }
+}
+
+/// The server's elapsed time, as far as this app can tell.
+#[derive(Resource, Default, Debug)]
+pub struct ServerClock {
+    /// Seconds to add to our elapsed time to get the server's, zero on the server.
+    offset: f32,
+}
+
+impl ServerClock {
+    pub fn now(&self, time: &Time) -> f32 {
+        time.elapsed_seconds() + self.offset
+    }
+}
+
+/// Sent to clients every [`CLOCK_SYNC_INTERVAL_SECONDS`] and when they connect.
+#[derive(Debug, Reflect, Serialize, Deserialize)]
+pub struct ClockSync {
+    pub server_seconds: f32,
+}
+
+fn send_clock_sync(
+    mut clock_syncs: EventWriter<ToClients<ClockSync>>,
+    mut server_events: EventReader<ServerEvent>,
+    time: Res<Time>,
+    mut last_sync: Local<f32>,
+) {
+    let server_seconds = time.elapsed_seconds();
+    if server_seconds - *last_sync >= CLOCK_SYNC_INTERVAL_SECONDS {
+        *last_sync = server_seconds;
+        clock_syncs.send(ToClients {
+            mode: SendMode::Broadcast,
+            event: ClockSync { server_seconds },
+        });
+    }
+    for event in server_events.iter() {
+        if let ServerEvent::ClientConnected(client_id, _) = event {
+            clock_syncs.send(ToClients {
+                mode: SendMode::Direct(*client_id),
+                event: ClockSync { server_seconds },
+            });
+        }
+    }
+}
+
+fn receive_clock_sync(
+    mut clock: ResMut<ServerClock>,
+    mut clock_syncs: EventReader<ClockSync>,
+    client: Res<RenetClient>,
+    time: Res<Time>,
+) {
+    let Some(sync) = clock_syncs.iter().last() else {
+        return;
+    };
+    // The sync took about half the round trip to get here, round trip time is in milliseconds
+    let travel_seconds = client.network_info().rtt as f32 / 1000.0 / 2.0;
+    clock.offset = sync.server_seconds + travel_seconds - time.elapsed_seconds();
+}
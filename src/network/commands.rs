@@ -1,8 +1,23 @@
 use crate::game_manager::GameState;
+use crate::game_mode::{ActiveGameMode, GameModeKind};
+use crate::map::{MapRotation, SelectedMap};
+use crate::network::auth::{decode_join_code, ConnectTokenIssuer};
+use crate::network::clock::ServerClock;
+use crate::network::handshake::{
+    truncate_password, ConnectionError, ConnectionRequest, LobbyPassword, RejectedClients,
+};
 use crate::network::matchmaking::{EphemeralMatchmakingLobby, MatchmakingState};
-use crate::network::{NetworkOwner, DEFAULT_PORT, MAX_CLIENTS, MAX_MESSAGE_SIZE, PROTOCOL_ID};
+use crate::network::protocol::{protocol_id, GAME_VERSION};
+use crate::network::{
+    DedicatedServer, NetworkOwner, DEFAULT_PORT, MAX_CLIENTS, MAX_MESSAGE_SIZE, MAX_SPECTATORS,
+};
 use crate::player::commands::SpawnPlayer;
+use crate::player::prediction::input_channel;
 use crate::player::{PlayerColor, Players};
+use crate::respawn::RespawnSettings;
+use crate::scoreboard::{MatchLength, RoundParticipants, Scoreboard};
+use crate::spectator::{JoinPolicy, QueuedForNextRound, Spectators};
+use crate::teams::TeamSettings;
 use bevy::ecs::system::{Command, SystemState};
 use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
@@ -14,25 +29,28 @@ use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
 use std::time::SystemTime;
 
 pub trait NetworkCommandsExt {
-    fn connect(&mut self, ip: IpAddr, bind: IpAddr, port: u16);
-    fn listen(&mut self, ip: IpAddr, bind: IpAddr, port: u16, server_name: String);
+    fn connect(&mut self, ip: IpAddr, bind: IpAddr, port: u16, spectate: bool);
+    /// Starts a server, see [`Listen`] for the settings.
+    fn listen(&mut self, listen: Listen);
     fn disconnect(&mut self);
 }
 
 impl<'w, 's> NetworkCommandsExt for Commands<'w, 's> {
-    fn connect(&mut self, ip: IpAddr, bind: IpAddr, port: u16) {
-        self.add(Connect { bind, ip, port });
-    }
-
-    fn listen(&mut self, ip: IpAddr, bind: IpAddr, port: u16, server_name: String) {
-        self.add(Listen {
+    fn connect(&mut self, ip: IpAddr, bind: IpAddr, port: u16, spectate: bool) {
+        self.add(Connect {
             bind,
-            port,
             ip,
-            server_name,
+            port,
+            join_code: None,
+            password: None,
+            spectate,
         });
     }
 
+    fn listen(&mut self, listen: Listen) {
+        self.add(listen);
+    }
+
     fn disconnect(&mut self) {
         self.add(Disconnect);
     }
@@ -43,6 +61,13 @@ pub struct Connect {
     pub bind: IpAddr,
     pub ip: IpAddr,
     pub port: u16,
+    /// Needed to join secure games, see [`ConnectTokenIssuer`].
+    pub join_code: Option<String>,
+    /// Needed to join games with a [`LobbyPassword`].
+    pub password: Option<String>,
+    /// Join without a ship to watch the game.
+    /// Not available with a join code, the host decides what is sent with those.
+    pub spectate: bool,
 }
 
 impl Default for Connect {
@@ -51,6 +76,9 @@ impl Default for Connect {
             bind: Ipv4Addr::new(0, 0, 0, 0).into(),
             ip: Ipv4Addr::new(127, 0, 0, 1).into(),
             port: DEFAULT_PORT,
+            join_code: None,
+            password: None,
+            spectate: false,
         }
     }
 }
@@ -66,6 +94,7 @@ impl Command for Connect {
             let mut receive_channels_config = network_channels.server_channels();
             apply_message_size_to_channels(&mut receive_channels_config);
             let mut send_channels_config = network_channels.client_channels();
+            send_channels_config.push(input_channel());
             apply_message_size_to_channels(&mut send_channels_config);
             let current_time = SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
@@ -73,11 +102,26 @@ impl Command for Connect {
             let client_id = current_time.as_millis() as u64;
             let server_addr = SocketAddr::new(self.ip, self.port);
             let socket = UdpSocket::bind((self.bind, 0)).expect("0.0.0.0 should be bindable");
-            let authentication = ClientAuthentication::Unsecure {
-                client_id,
-                protocol_id: PROTOCOL_ID,
-                server_addr,
-                user_data: None,
+            let authentication = match &self.join_code {
+                Some(join_code) => match decode_join_code(join_code) {
+                    Ok(connect_token) => ClientAuthentication::Secure { connect_token },
+                    Err(e) => {
+                        error!("Could not read join code: {e}");
+                        return;
+                    }
+                },
+                None => ClientAuthentication::Unsecure {
+                    client_id,
+                    protocol_id: protocol_id(),
+                    server_addr,
+                    user_data: Some(
+                        ConnectionRequest {
+                            password: self.password.clone(),
+                            spectate: self.spectate,
+                        }
+                        .to_user_data(),
+                    ),
+                },
             };
 
             let connection_config = RenetConnectionConfig {
@@ -85,13 +129,16 @@ impl Command for Connect {
                 receive_channels_config,
                 ..Default::default()
             };
-            primary_window.single_mut().title = "Client".to_string();
+            if let Ok(mut window) = primary_window.get_single_mut() {
+                window.title = "Client".to_string();
+            }
 
             RenetClient::new(current_time, socket, connection_config, authentication).unwrap()
         };
         world
             .resource_mut::<NextState<GameState>>()
             .set(GameState::PreGame);
+        world.remove_resource::<ConnectionError>();
         world.insert_resource(client);
     }
 }
@@ -101,10 +148,24 @@ pub struct Listen {
     pub ip: IpAddr,
     pub port: u16,
     pub server_name: String,
+    /// Only allow clients with a join code from [`ConnectTokenIssuer`] to connect.
+    pub secure: bool,
+    /// Clients without a join code have to send this password to join.
+    pub password: Option<String>,
+    /// The name of the map to play, or keep the current [`SelectedMap`] if `None`.
+    pub map: Option<String>,
+    /// Switch to the next of these maps after every round. Leave empty to keep playing one map.
+    pub map_rotation: Vec<String>,
+    pub teams: TeamSettings,
+    pub game_mode: GameModeKind,
+    pub match_length: MatchLength,
+    pub join_policy: JoinPolicy,
+    pub respawn: RespawnSettings,
 }
 
 impl Command for Listen {
     fn write(self, world: &mut World) {
+        let mut token_issuer = None;
         let server = {
             let mut state = SystemState::<(
                 Res<NetworkChannels>,
@@ -114,6 +175,7 @@ impl Command for Listen {
             let mut send_channels_config = network_channels.server_channels();
             apply_message_size_to_channels(&mut send_channels_config);
             let mut receive_channels_config = network_channels.client_channels();
+            receive_channels_config.push(input_channel());
             apply_message_size_to_channels(&mut receive_channels_config);
 
             let current_time = SystemTime::now()
@@ -122,11 +184,21 @@ impl Command for Listen {
             let server_addr = SocketAddr::new(self.bind, self.port);
             let socket = UdpSocket::bind(server_addr).unwrap();
             let public_addr = SocketAddr::new(self.ip, self.port);
+            let authentication = if self.secure {
+                let issuer = ConnectTokenIssuer::new(public_addr);
+                let authentication = ServerAuthentication::Secure {
+                    private_key: issuer.private_key(),
+                };
+                token_issuer = Some(issuer);
+                authentication
+            } else {
+                ServerAuthentication::Unsecure
+            };
             let server_config = ServerConfig::new(
-                MAX_CLIENTS,
-                PROTOCOL_ID,
+                MAX_CLIENTS + MAX_SPECTATORS,
+                protocol_id(),
                 public_addr,
-                ServerAuthentication::Unsecure,
+                authentication,
             );
 
             let connection_config = RenetConnectionConfig {
@@ -135,28 +207,60 @@ impl Command for Listen {
                 ..Default::default()
             };
 
-            primary_window.single_mut().title = "Server".to_string();
+            if let Ok(mut window) = primary_window.get_single_mut() {
+                window.title = "Server".to_string();
+            }
 
             RenetServer::new(current_time, server_config, connection_config, socket).unwrap()
         };
         world.insert_resource(server);
+        if let Some(map) = self.map {
+            world.insert_resource(SelectedMap(map));
+        }
+        if !self.map_rotation.is_empty() {
+            world.insert_resource(MapRotation {
+                maps: self.map_rotation,
+            });
+        }
+        // Before spawning the host's player so they get a team
+        world.insert_resource(self.teams);
+        world.insert_resource(ActiveGameMode(self.game_mode.create()));
+        world.insert_resource(Scoreboard::new(self.match_length));
+        world.insert_resource(RoundParticipants::default());
+        world.insert_resource(self.join_policy);
+        world.insert_resource(self.respawn);
+        if let Some(token_issuer) = token_issuer {
+            world.insert_resource(token_issuer);
+        }
+        let has_password = self.password.is_some();
+        if let Some(password) = self.password {
+            world.insert_resource(LobbyPassword(truncate_password(&password).to_string()));
+        }
         world
             .resource_mut::<NextState<GameState>>()
             .set(GameState::PreGame);
-        SpawnPlayer {
-            color: PlayerColor::get(0),
-            network_owner: NetworkOwner(SERVER_ID),
+        let dedicated = world.contains_resource::<DedicatedServer>();
+        if !dedicated {
+            SpawnPlayer {
+                color: PlayerColor::get(0),
+                network_owner: NetworkOwner(SERVER_ID),
+                respawn: false,
+            }
+            .write(world);
         }
-        .write(world);
+        let map = world.resource::<SelectedMap>().0.clone();
         let mut mm_state = world.resource_mut::<MatchmakingState>();
         mm_state.lobby = Some(EphemeralMatchmakingLobby {
             ip: self.ip.to_string(),
             name: self.server_name,
             player_capacity: MAX_CLIENTS as u8,
-            slots_occupied: 1,
+            slots_occupied: u8::from(!dedicated),
             auto_restart: true,
-            has_password: false,
+            has_password,
             last_updated: 0,
+            protocol_id: protocol_id(),
+            version: GAME_VERSION.to_string(),
+            map,
         });
         mm_state.lobby_public = self.ip.is_global_unstable();
     }
@@ -170,20 +274,27 @@ impl Command for Disconnect {
         debug!("Disconnecting");
         if let Some(mut server) = world.get_resource_mut::<RenetServer>() {
             server.disconnect_clients();
-            world
-                .resource_mut::<NextState<GameState>>()
-                .set(GameState::MainMenu);
         }
 
         if let Some(mut client) = world.get_resource_mut::<RenetClient>() {
             client.disconnect();
-            world
-                .resource_mut::<NextState<GameState>>()
-                .set(GameState::MainMenu);
         }
+        // Also return to the menu without a client or server, e.g. from a version mismatch screen
+        world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::MainMenu);
         world.resource_mut::<Players>().reset();
         world.remove_resource::<RenetServer>();
         world.remove_resource::<RenetClient>();
+        world.remove_resource::<ConnectTokenIssuer>();
+        world.remove_resource::<LobbyPassword>();
+        world.remove_resource::<MapRotation>();
+        world.insert_resource(Scoreboard::default());
+        world.remove_resource::<ConnectionError>();
+        world.insert_resource(RejectedClients::default());
+        world.insert_resource(Spectators::default());
+        world.remove_resource::<QueuedForNextRound>();
+        world.insert_resource(ServerClock::default());
     }
 }
 
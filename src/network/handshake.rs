@@ -0,0 +1,257 @@
+use crate::game_manager::GameState;
+use crate::network::auth::ConnectTokenIssuer;
+use crate::network::{is_client, is_server};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_replicon::prelude::*;
+use bevy_replicon::renet::{ServerEvent, NETCODE_USER_DATA_BYTES};
+use serde::{Deserialize, Serialize};
+use std::fmt::Formatter;
+
+/// How long to wait after telling a client why they were rejected before disconnecting them.
+/// Disconnecting right away would drop the message before it is sent.
+const REJECT_DISCONNECT_DELAY_SECONDS: f32 = 1.0;
+pub const MAX_PASSWORD_LENGTH: usize = 64;
+/// Where in the `user_data` the spectate flag is stored, right after the longest password.
+const SPECTATE_FLAG_INDEX: usize = 2 + MAX_PASSWORD_LENGTH;
+
+pub struct HandshakePlugin;
+
+impl Plugin for HandshakePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RejectedClients>();
+        app.add_server_event::<ConnectionRejected>();
+        app.add_system(
+            validate_connecting_clients
+                .run_if(is_server())
+                .in_set(ValidateConnections),
+        );
+        app.add_system(disconnect_rejected_clients.run_if(is_server()));
+        app.add_system(receive_connection_rejected.run_if(is_client()));
+        app.add_system(
+            detect_failed_connection
+                .run_if(is_client())
+                .in_set(OnUpdate(GameState::PreGame)),
+        );
+    }
+}
+
+/// Systems that react to [`ServerEvent::ClientConnected`] should run after this so they can skip
+/// clients in [`RejectedClients`].
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct ValidateConnections;
+
+/// Cuts `password` down to at most [`MAX_PASSWORD_LENGTH`] bytes without splitting a character,
+/// so it still fits in the `user_data` and arrives the same as it was typed.
+pub fn truncate_password(password: &str) -> &str {
+    let mut len = password.len().min(MAX_PASSWORD_LENGTH);
+    while !password.is_char_boundary(len) {
+        len -= 1;
+    }
+    &password[..len]
+}
+
+/// Only exists on the host if the lobby was created with a password.
+#[derive(Resource, Debug, Clone)]
+pub struct LobbyPassword(pub String);
+
+/// What the client sends to the server in the netcode `user_data` when connecting.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionRequest {
+    pub password: Option<String>,
+    /// Join without a ship, only watching the other players.
+    pub spectate: bool,
+}
+
+impl ConnectionRequest {
+    pub fn to_user_data(&self) -> [u8; NETCODE_USER_DATA_BYTES] {
+        let mut user_data = [0; NETCODE_USER_DATA_BYTES];
+        if let Some(password) = &self.password {
+            let bytes = truncate_password(password).as_bytes();
+            let len = bytes.len();
+            user_data[0] = 1;
+            user_data[1] = len as u8;
+            user_data[2..2 + len].copy_from_slice(&bytes[..len]);
+        }
+        user_data[SPECTATE_FLAG_INDEX] = u8::from(self.spectate);
+        user_data
+    }
+
+    pub fn from_user_data(user_data: &[u8; NETCODE_USER_DATA_BYTES]) -> Self {
+        let password = (user_data[0] == 1).then(|| {
+            let len = (user_data[1] as usize).min(MAX_PASSWORD_LENGTH);
+            String::from_utf8_lossy(&user_data[2..2 + len]).to_string()
+        });
+        Self {
+            password,
+            spectate: user_data[SPECTATE_FLAG_INDEX] == 1,
+        }
+    }
+}
+
+/// Sent to a client right before the server disconnects them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum ConnectionRejected {
+    WrongPassword,
+}
+
+impl std::fmt::Display for ConnectionRejected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionRejected::WrongPassword => write!(f, "Wrong password"),
+        }
+    }
+}
+
+/// Clients that failed validation and will be disconnected, with the time to disconnect them.
+#[derive(Resource, Default, Debug)]
+pub struct RejectedClients {
+    clients: HashMap<u64, f32>,
+}
+
+impl RejectedClients {
+    pub fn contains(&self, client_id: u64) -> bool {
+        self.clients.contains_key(&client_id)
+    }
+}
+
+/// Set on the client when we can't join a server, with the reason to show the player.
+#[derive(Resource, Debug, Clone)]
+pub struct ConnectionError(pub String);
+
+fn validate_connecting_clients(
+    mut events: EventReader<ServerEvent>,
+    mut rejected_clients: ResMut<RejectedClients>,
+    mut rejections: EventWriter<ToClients<ConnectionRejected>>,
+    password: Option<Res<LobbyPassword>>,
+    token_issuer: Option<Res<ConnectTokenIssuer>>,
+    time: Res<Time>,
+) {
+    for event in events.iter() {
+        let ServerEvent::ClientConnected(client_id, user_data) = event else {
+            continue;
+        };
+        let request = ConnectionRequest::from_user_data(user_data);
+
+        // Players with a join code were invited by the host, so they don't need the password.
+        let rejection = match &password {
+            Some(password) if token_issuer.is_none() => (request.password.as_deref()
+                != Some(password.0.as_str()))
+            .then_some(ConnectionRejected::WrongPassword),
+            _ => None,
+        };
+
+        if let Some(rejection) = rejection {
+            info!("Rejecting client {client_id}: {rejection}");
+            rejections.send(ToClients {
+                mode: SendMode::Direct(*client_id),
+                event: rejection,
+            });
+            rejected_clients.clients.insert(
+                *client_id,
+                time.elapsed_seconds() + REJECT_DISCONNECT_DELAY_SECONDS,
+            );
+        }
+    }
+}
+
+fn disconnect_rejected_clients(
+    mut events: EventReader<ServerEvent>,
+    mut rejected_clients: ResMut<RejectedClients>,
+    mut server: ResMut<RenetServer>,
+    time: Res<Time>,
+) {
+    for event in events.iter() {
+        if let ServerEvent::ClientDisconnected(client_id) = event {
+            rejected_clients.clients.remove(client_id);
+        }
+    }
+    for (client_id, disconnect_at) in rejected_clients.clients.iter() {
+        if time.elapsed_seconds() >= *disconnect_at {
+            server.disconnect(*client_id);
+        }
+    }
+}
+
+fn receive_connection_rejected(
+    mut commands: Commands,
+    mut events: EventReader<ConnectionRejected>,
+    game_state: Res<State<GameState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    for event in events.iter() {
+        warn!("Server rejected connection: {event}");
+        commands.insert_resource(ConnectionError(event.to_string()));
+        if game_state.0 != GameState::PreGame {
+            next_game_state.set(GameState::PreGame);
+        }
+    }
+}
+
+/// Shows why the connection failed instead of waiting for a connection forever.
+/// Servers running a different version drop our connection requests without a reply, so from
+/// here that looks the same as a server that is offline.
+fn detect_failed_connection(
+    mut commands: Commands,
+    client: Res<RenetClient>,
+    connection_error: Option<Res<ConnectionError>>,
+) {
+    if connection_error.is_some() {
+        return;
+    }
+    if let Some(reason) = client.disconnected() {
+        warn!("Failed to connect: {reason:?}");
+        commands.insert_resource(ConnectionError(format!(
+            "{reason:?}. The server may be offline or running a different version."
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connection_request_round_trips_through_user_data() {
+        for request in [
+            ConnectionRequest::default(),
+            ConnectionRequest {
+                password: Some("hunter2".to_string()),
+                spectate: true,
+            },
+            ConnectionRequest {
+                password: Some(String::new()),
+                spectate: false,
+            },
+            ConnectionRequest {
+                password: Some("pässwörd 🚀".to_string()),
+                spectate: false,
+            },
+        ] {
+            let user_data = request.to_user_data();
+            assert_eq!(ConnectionRequest::from_user_data(&user_data), request);
+        }
+    }
+
+    #[test]
+    fn long_passwords_are_cut_at_a_char_boundary() {
+        let password = "é".repeat(MAX_PASSWORD_LENGTH);
+        let truncated = truncate_password(&password);
+        assert!(truncated.len() <= MAX_PASSWORD_LENGTH);
+        assert_eq!(truncated, "é".repeat(MAX_PASSWORD_LENGTH / 2));
+
+        let request = ConnectionRequest {
+            password: Some(password.clone()),
+            spectate: true,
+        };
+        let received = ConnectionRequest::from_user_data(&request.to_user_data());
+        assert_eq!(received.password.as_deref(), Some(truncated));
+        assert!(received.spectate);
+    }
+
+    #[test]
+    fn short_passwords_are_not_truncated() {
+        assert_eq!(truncate_password("abc"), "abc");
+        assert_eq!(truncate_password(""), "");
+    }
+}
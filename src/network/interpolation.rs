@@ -0,0 +1,157 @@
+use crate::network::{is_client, NetworkOwner};
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::{RigidBody, Velocity};
+use bevy_replicon::prelude::*;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+pub const DEFAULT_INTERPOLATION_DELAY_MILLIS: u64 = 100;
+const DEFAULT_MAX_EXTRAPOLATION_MILLIS: u64 = 250;
+/// Old snapshots are dropped as soon as they are no longer needed, this is just a safety net.
+const MAX_SNAPSHOTS: usize = 32;
+/// Moving further than this between two snapshots is a teleport (e.g. a respawn),
+/// so don't interpolate between them.
+const TELEPORT_DISTANCE: f32 = 200.0;
+
+/// Smooths out the movement of entities simulated on the server.
+///
+/// Replicated transforms only arrive at the server's tick rate, and sometimes late or not at all.
+/// Instead of applying them directly, clients buffer them and render entities
+/// [`InterpolationSettings::delay`] in the past, blending between the two snapshots around that
+/// time. If no newer snapshot has arrived yet, the entity keeps moving with its replicated
+/// [`Velocity`] for up to [`InterpolationSettings::max_extrapolation`].
+///
+/// The player we control is excluded, it is handled by client side prediction instead.
+pub struct InterpolationPlugin;
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<InterpolationSettings>();
+        app.init_resource::<InterpolationSettings>();
+        app.add_systems(
+            (
+                add_snapshot_buffer,
+                interpolate_transforms,
+                record_rendered_transforms.in_base_set(CoreSet::Last),
+            )
+                .distributive_run_if(is_client()),
+        );
+    }
+}
+
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct InterpolationSettings {
+    /// How far behind the newest snapshot entities are rendered.
+    /// Should be at least a couple of server ticks so there is always a snapshot to blend to.
+    pub delay: Duration,
+    /// How long to keep extrapolating when snapshots stop arriving.
+    pub max_extrapolation: Duration,
+}
+
+impl Default for InterpolationSettings {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_millis(DEFAULT_INTERPOLATION_DELAY_MILLIS),
+            max_extrapolation: Duration::from_millis(DEFAULT_MAX_EXTRAPOLATION_MILLIS),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Snapshot {
+    received: f32,
+    translation: Vec3,
+    rotation: Quat,
+    velocity: Velocity,
+}
+
+#[derive(Component, Default, Debug)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<Snapshot>,
+    /// The transform at the end of last frame.
+    /// If the transform is different at the start of this frame, the server sent a new one.
+    rendered: Option<Transform>,
+}
+
+/// Interpolates server simulated physics objects: other players, asteroids and powerups.
+///
+/// They are switched to kinematic bodies so the local physics simulation doesn't fight with the
+/// interpolated positions.
+fn add_snapshot_buffer(
+    mut commands: Commands,
+    mut query: Query<
+        (Entity, &mut RigidBody, Option<&NetworkOwner>),
+        (With<Replication>, Without<SnapshotBuffer>),
+    >,
+    client: Res<RenetClient>,
+) {
+    for (entity, mut rigid_body, owner) in query.iter_mut() {
+        if owner.map_or(false, |owner| owner.0 == client.client_id()) {
+            continue;
+        }
+        *rigid_body = RigidBody::KinematicPositionBased;
+        commands.entity(entity).insert(SnapshotBuffer::default());
+    }
+}
+
+fn interpolate_transforms(
+    mut query: Query<(&mut Transform, &Velocity, &mut SnapshotBuffer)>,
+    settings: Res<InterpolationSettings>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for (mut transform, velocity, mut buffer) in query.iter_mut() {
+        if buffer.rendered != Some(*transform) {
+            let snapshot = Snapshot {
+                received: now,
+                translation: transform.translation,
+                rotation: transform.rotation,
+                velocity: *velocity,
+            };
+            let teleported = buffer.snapshots.back().map_or(false, |last| {
+                last.translation.distance(snapshot.translation) > TELEPORT_DISTANCE
+            });
+            if teleported || buffer.snapshots.len() >= MAX_SNAPSHOTS {
+                buffer.snapshots.clear();
+            }
+            buffer.snapshots.push_back(snapshot);
+        }
+
+        let render_time = now - settings.delay.as_secs_f32();
+        // Keep the newest snapshot from before the render time to interpolate from
+        while buffer.snapshots.len() >= 2 && buffer.snapshots[1].received <= render_time {
+            buffer.snapshots.pop_front();
+        }
+
+        let (translation, rotation) = match (buffer.snapshots.front(), buffer.snapshots.get(1)) {
+            (Some(from), Some(to)) if from.received <= render_time => {
+                let t = (render_time - from.received) / (to.received - from.received);
+                (
+                    from.translation.lerp(to.translation, t),
+                    from.rotation.slerp(to.rotation, t),
+                )
+            }
+            (Some(from), None) if from.received < render_time => {
+                let elapsed =
+                    (render_time - from.received).min(settings.max_extrapolation.as_secs_f32());
+                (
+                    from.translation + from.velocity.linvel.extend(0.0) * elapsed,
+                    from.rotation * Quat::from_rotation_z(from.velocity.angvel * elapsed),
+                )
+            }
+            // Nothing old enough to show yet, hold at the oldest snapshot we have
+            (Some(from), _) => (from.translation, from.rotation),
+            (None, _) => continue,
+        };
+        transform.translation = translation;
+        transform.rotation = rotation;
+    }
+}
+
+/// Runs after physics so its writeback isn't mistaken for a new snapshot.
+fn record_rendered_transforms(mut query: Query<(&Transform, &mut SnapshotBuffer)>) {
+    for (transform, mut buffer) in query.iter_mut() {
+        buffer.rendered = Some(*transform);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::asteroid::Asteroid;
+use crate::network::{is_server, NetworkOwner};
+use crate::player::prediction::PlayerInput;
+use crate::player::weapons::Projectile;
+use crate::player::Player;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::{Collider, QueryFilter, RapierContext, RayIntersection};
+use bevy_replicon::prelude::*;
+use std::collections::VecDeque;
+use std::f32::consts::{PI, TAU};
+use std::time::Duration;
+
+const DEFAULT_MAX_REWIND_MILLIS: u64 = 200;
+
+/// Lets players aim at what they see instead of leading their shots by their ping.
+///
+/// The server remembers where players and asteroids were for the last
+/// [`LagCompensationSettings::max_rewind`]. Lasers remember how far behind the server their
+/// shooter's view was when they fired, and hit detection checks them against where things were
+/// at that time.
+pub struct LagCompensationPlugin;
+
+impl Plugin for LagCompensationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LagCompensationSettings>();
+        app.init_resource::<LagCompensationSettings>();
+        app.add_systems(
+            (
+                add_collider_history,
+                record_collider_history,
+                update_view_delay,
+                rewind_lasers_to_shooter_view,
+            )
+                .distributive_run_if(is_server()),
+        );
+    }
+}
+
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct LagCompensationSettings {
+    /// Players with more lag than this will have to lead their shots by the difference.
+    /// Keeps players with a very bad connection from hitting others long after they took cover.
+    pub max_rewind: Duration,
+}
+
+impl Default for LagCompensationSettings {
+    fn default() -> Self {
+        Self {
+            max_rewind: Duration::from_millis(DEFAULT_MAX_REWIND_MILLIS),
+        }
+    }
+}
+
+/// Where an entity's collider was over the last [`LagCompensationSettings::max_rewind`].
+#[derive(Component, Default, Debug)]
+pub struct ColliderHistory {
+    /// Elapsed time, translation and rotation, oldest first.
+    samples: VecDeque<(f32, Vec2, f32)>,
+}
+
+impl ColliderHistory {
+    /// The position and rotation at `time`, interpolating between the recorded frames.
+    pub fn at(&self, time: f32) -> Option<(Vec2, f32)> {
+        let newer_index = self
+            .samples
+            .iter()
+            .position(|(sample_time, _, _)| *sample_time >= time);
+        match newer_index {
+            // Asking for a time after the newest sample, or before the oldest
+            None => self.samples.back().map(|(_, t, r)| (*t, *r)),
+            Some(0) => self.samples.front().map(|(_, t, r)| (*t, *r)),
+            Some(index) => {
+                let (from_time, from_translation, from_rotation) = self.samples[index - 1];
+                let (to_time, to_translation, to_rotation) = self.samples[index];
+                let t = (time - from_time) / (to_time - from_time);
+                let rotation_delta = (to_rotation - from_rotation + PI).rem_euclid(TAU) - PI;
+                Some((
+                    from_translation.lerp(to_translation, t),
+                    from_rotation + rotation_delta * t,
+                ))
+            }
+        }
+    }
+}
+
+/// How far behind the server the owning client sees the world, in seconds.
+/// Ships get one when they spawn, it is kept up to date from their owner's input.
+#[derive(Component, Default, Debug, Copy, Clone)]
+pub struct ViewDelay(pub f32);
+
+/// How far back in time a laser checks for hits, in seconds.
+#[derive(Component, Default, Debug, Copy, Clone)]
+pub struct Rewind(pub f32);
+
+fn add_collider_history(
+    mut commands: Commands,
+    query: Query<
+        Entity,
+        (
+            Or<(With<Player>, With<Asteroid>)>,
+            With<Collider>,
+            Without<ColliderHistory>,
+        ),
+    >,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert(ColliderHistory::default());
+    }
+}
+
+fn record_collider_history(
+    mut query: Query<(&Transform, &mut ColliderHistory)>,
+    settings: Res<LagCompensationSettings>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    let oldest = now - settings.max_rewind.as_secs_f32();
+    for (transform, mut history) in query.iter_mut() {
+        // Keep one sample older than the window so the oldest time can still be interpolated
+        while history.samples.len() >= 2 && history.samples[1].0 <= oldest {
+            history.samples.pop_front();
+        }
+        let (_, _, rotation) = transform.rotation.to_euler(EulerRot::XYZ);
+        history
+            .samples
+            .push_back((now, transform.translation.xy(), rotation));
+    }
+}
+
+/// Clients see other entities one round trip plus their interpolation delay in the past:
+/// the snapshot took half the round trip to reach them, and their input half of it to get back.
+fn update_view_delay(
+    mut inputs: EventReader<FromClient<PlayerInput>>,
+    mut players: Query<(&NetworkOwner, &mut ViewDelay), With<Player>>,
+    server: Res<RenetServer>,
+) {
+    for FromClient { client_id, event } in inputs.iter() {
+        let Some((_, mut view_delay)) = players.iter_mut().find(|(owner, _)| owner.0 == *client_id)
+        else {
+            continue;
+        };
+        // Round trip time is reported in milliseconds
+        let round_trip = server
+            .network_info(*client_id)
+            .map_or(0.0, |info| info.rtt as f32 / 1000.0);
+        let interpolation_delay = event.interpolation_delay_millis as f32 / 1000.0;
+        view_delay.0 = round_trip + interpolation_delay;
+    }
+}
+
+fn rewind_lasers_to_shooter_view(
+    mut commands: Commands,
+    lasers: Query<(Entity, &NetworkOwner), Added<Projectile>>,
+    shooters: Query<(&NetworkOwner, &ViewDelay), With<Player>>,
+    settings: Res<LagCompensationSettings>,
+) {
+    for (entity, laser_owner) in lasers.iter() {
+        let Some((_, view_delay)) = shooters.iter().find(|(owner, _)| *owner == laser_owner) else {
+            continue;
+        };
+        let rewind = view_delay.0.min(settings.max_rewind.as_secs_f32());
+        commands.entity(entity).insert(Rewind(rewind));
+    }
+}
+
+/// Casts a ray against the world as it was at `time`.
+///
+/// Entities with a [`ColliderHistory`] are checked at their recorded positions, everything else
+/// at its current position. The `shooter` is never hit, the ray starts inside of it.
+pub fn cast_ray_at_time(
+    rapier_context: &RapierContext,
+    histories: &Query<(Entity, &Collider, &ColliderHistory)>,
+    time: f32,
+    ray_origin: Vec2,
+    ray_direction: Vec2,
+    max_toi: f32,
+    shooter: Option<Entity>,
+) -> Option<(Entity, RayIntersection)> {
+    let is_rewound = |entity: Entity| histories.contains(entity);
+    let mut closest = rapier_context.cast_ray_and_get_normal(
+        ray_origin,
+        ray_direction,
+        max_toi,
+        true,
+        QueryFilter::default()
+            .exclude_sensors()
+            .predicate(&|entity| Some(entity) != shooter && !is_rewound(entity)),
+    );
+
+    for (entity, collider, history) in histories.iter() {
+        if Some(entity) == shooter {
+            continue;
+        }
+        let Some((translation, rotation)) = history.at(time) else {
+            continue;
+        };
+        let Some(intersection) = collider.cast_ray_and_get_normal(
+            translation,
+            rotation,
+            ray_origin,
+            ray_direction,
+            max_toi,
+            true,
+        ) else {
+            continue;
+        };
+        if closest.map_or(true, |(_, closest)| intersection.toi < closest.toi) {
+            closest = Some((entity, intersection));
+        }
+    }
+    closest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(samples: &[(f32, Vec2, f32)]) -> ColliderHistory {
+        ColliderHistory {
+            samples: samples.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn empty_history_has_no_position() {
+        assert_eq!(ColliderHistory::default().at(1.0), None);
+    }
+
+    #[test]
+    fn interpolates_between_samples() {
+        let history = history(&[(1.0, Vec2::ZERO, 0.0), (2.0, Vec2::new(10.0, -4.0), 1.0)]);
+        let (translation, rotation) = history.at(1.25).unwrap();
+        assert!(translation.abs_diff_eq(Vec2::new(2.5, -1.0), 1e-5));
+        assert!((rotation - 0.25).abs() < 1e-5);
+        assert_eq!(history.at(2.0), Some((Vec2::new(10.0, -4.0), 1.0)));
+    }
+
+    #[test]
+    fn clamps_to_the_oldest_and_newest_samples() {
+        let history = history(&[(1.0, Vec2::ONE, 0.5), (2.0, Vec2::X, 1.5)]);
+        assert_eq!(history.at(0.0), Some((Vec2::ONE, 0.5)));
+        assert_eq!(history.at(3.0), Some((Vec2::X, 1.5)));
+    }
+
+    #[test]
+    fn rotation_takes_the_short_way_around() {
+        let history = history(&[(0.0, Vec2::ZERO, PI - 0.1), (1.0, Vec2::ZERO, -PI + 0.1)]);
+        let (_, rotation) = history.at(0.5).unwrap();
+        assert!((rotation - PI).abs() < 1e-5);
+    }
+}
@@ -1,4 +1,6 @@
 use crate::game_manager::GameState;
+use crate::map::SelectedMap;
+use crate::network::protocol::protocol_id;
 use crate::player::Player;
 use crate::ui::Menu;
 use bevy::{prelude::*, utils::HashMap};
@@ -51,6 +53,23 @@ pub struct EphemeralMatchmakingLobby {
     pub has_password: bool,
     #[serde(alias = "lastUpdated")]
     pub last_updated: u64,
+    /// See [`protocol_id`](crate::network::protocol::protocol_id).
+    /// Zero if the lobby was posted by a version that didn't send it.
+    #[serde(alias = "protocolId", default)]
+    pub protocol_id: u64,
+    #[serde(default)]
+    pub version: String,
+    /// The map currently being played.
+    #[serde(default)]
+    pub map: String,
+}
+
+impl EphemeralMatchmakingLobby {
+    /// Whether we can join this lobby without a version mismatch.
+    /// Lobbies that don't list a protocol id are assumed to be compatible.
+    pub fn is_compatible(&self) -> bool {
+        self.protocol_id == 0 || self.protocol_id == protocol_id()
+    }
 }
 
 pub fn update_matchmaking_state(
@@ -58,7 +77,7 @@ pub fn update_matchmaking_state(
     mut cmds: Commands,
     time: Res<Time>,
     client: ResMut<ReqwestClient>,
-    menu_state: Res<State<Menu>>,
+    menu_state: Option<Res<State<Menu>>>,
 ) {
     mm_res.timer.tick(time.delta());
 
@@ -89,7 +108,11 @@ pub fn update_matchmaking_state(
             };
         }
 
-        if menu_state.0 == Menu::LobbyBrowser {
+        // Dedicated servers have no menus, so they never need the server list.
+        if menu_state
+            .map(|s| s.0 == Menu::LobbyBrowser)
+            .unwrap_or_default()
+        {
             if let Ok(getreq) = client.0.get(url).build() {
                 cmds.spawn(ReqwestRequest(Some(getreq))).insert(GetLobbyReq);
             } else {
@@ -147,7 +170,16 @@ fn remove_lobby_info(mut matchmaking_state: ResMut<MatchmakingState>) {
     matchmaking_state.lobby = None;
 }
 
-fn update_lobby_info(mut matchmaking_state: ResMut<MatchmakingState>, query: Query<With<Player>>) {
+fn update_lobby_info(
+    mut matchmaking_state: ResMut<MatchmakingState>,
+    query: Query<With<Player>>,
+    selected_map: Res<SelectedMap>,
+) {
+    if selected_map.is_changed() {
+        if let Some(lobby) = matchmaking_state.lobby.as_mut() {
+            lobby.map = selected_map.0.clone();
+        }
+    }
     let player_count = query.iter().count();
     if player_count
         != matchmaking_state
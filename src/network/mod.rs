@@ -1,16 +1,21 @@
+pub mod auth;
+pub mod clock;
 pub mod commands;
 #[cfg(feature = "bevy_editor_pls")]
 mod editor;
+pub mod handshake;
+pub mod interpolation;
+pub mod lag_compensation;
 pub mod matchmaking;
+pub mod protocol;
 
 use async_compat::Compat;
-use std::fmt::Debug;
 use std::net::{IpAddr, Ipv4Addr};
 
-use crate::asteroid::Asteroid;
+use crate::asteroid::{Asteroid, LevelAsteroid};
 use crate::bundles::lyon_rendering::roid_paths::RoidPath;
 use crate::game_manager::GameState;
-use crate::player::{Player, PlayerAction, Thruster};
+use crate::player::{Player, Thruster};
 use bevy::prelude::*;
 use bevy::tasks::{AsyncComputeTaskPool, Task};
 use bevy_rapier2d::prelude::Velocity;
@@ -18,16 +23,18 @@ use bevy_replicon::prelude::*;
 use bevy_replicon::renet::ServerEvent;
 use bevy_replicon::ReplicationPlugins;
 use futures_lite::future;
-use leafwing_input_manager::action_state::{ActionDiff, ActionState};
-use leafwing_input_manager::systems::generate_action_diffs;
-use leafwing_input_manager::Actionlike;
 use serde::{Deserialize, Serialize};
 
+use self::clock::ClockPlugin;
+use self::handshake::{ConnectionError, HandshakePlugin};
+use self::interpolation::InterpolationPlugin;
+use self::lag_compensation::LagCompensationPlugin;
 use self::matchmaking::MatchmakingPlugin;
 
 pub const DEFAULT_PORT: u16 = 4761;
-pub const PROTOCOL_ID: u64 = 0;
 pub const MAX_CLIENTS: usize = 6;
+/// Extra connections the server accepts on top of [`MAX_CLIENTS`] for players that only watch.
+pub const MAX_SPECTATORS: usize = 4;
 pub const MAX_MESSAGE_SIZE: u64 = 40000;
 
 #[derive(Resource)]
@@ -95,12 +102,17 @@ impl Plugin for NetworkPlugin {
                 .set(ServerPlugin { tick_rate: 30 }),
         );
         app.add_plugin(MatchmakingPlugin);
+        app.add_plugin(HandshakePlugin);
+        app.add_plugin(ClockPlugin);
+        app.add_plugin(InterpolationPlugin);
+        app.add_plugin(LagCompensationPlugin);
         app.register_type::<NetworkOwner>();
         app.register_type::<RoidPath>();
         app.replicate::<Transform>();
         app.replicate::<Player>();
         app.replicate::<NetworkOwner>();
-        app.replicate::<Asteroid>();
+        app.register_type::<Asteroid>();
+        app.replicate::<LevelAsteroid>();
         app.replicate::<Velocity>();
         app.replicate::<Thruster>();
         app.add_system(log_network_events);
@@ -109,13 +121,11 @@ impl Plugin for NetworkPlugin {
                 .run_if(is_client())
                 .in_set(OnUpdate(GameState::PreGame)),
         );
-        app.add_client_event::<ActionDiff<PlayerAction, NetworkOwner>>();
-        app.add_system(
-            generate_action_diffs::<PlayerAction, NetworkOwner>.in_base_set(CoreSet::PostUpdate),
-        );
         app.add_system(poll_public_ip_task);
         app.add_system(
-            process_action_diffs::<PlayerAction, NetworkOwner>.in_base_set(CoreSet::PreUpdate),
+            auth::log_join_codes
+                .run_if(is_dedicated_server())
+                .run_if(resource_exists::<auth::ConnectTokenIssuer>()),
         );
 
         #[cfg(feature = "bevy_editor_pls")]
@@ -131,39 +141,17 @@ pub fn is_client() -> impl FnMut(Option<Res<RenetClient>>) -> bool + Clone {
     move |res: Option<Res<RenetClient>>| res.is_some()
 }
 
-/// This is the same as [`leafwing_input_manager::systems::process_action_diffs`] but
-/// uses [`FromClient`] to read the events
-pub fn process_action_diffs<A: Actionlike + Debug, ID: Eq + Component + Clone + Debug>(
-    mut action_state_query: Query<(&mut ActionState<A>, &ID)>,
-    mut action_diffs: EventReader<FromClient<ActionDiff<A, ID>>>,
-) {
-    // PERF: This would probably be faster with an index, but is much more fussy
-    for action_diff in action_diffs.iter() {
-        let action_diff = &action_diff.event;
-        for (mut action_state, id) in action_state_query.iter_mut() {
-            debug!("{:?}", action_diff);
-            match action_diff {
-                ActionDiff::Pressed {
-                    action,
-                    id: event_id,
-                } => {
-                    if event_id == id {
-                        action_state.press(action.clone());
-                        continue;
-                    }
-                }
-                ActionDiff::Released {
-                    action,
-                    id: event_id,
-                } => {
-                    if event_id == id {
-                        action_state.release(action.clone());
-                        continue;
-                    }
-                }
-            };
-        }
-    }
+/// Inserted when running with `--dedicated`.
+/// The app has no window, renderer or audio, and the server doesn't control a player.
+#[derive(Resource, Default, Debug)]
+pub struct DedicatedServer;
+
+pub fn is_dedicated_server() -> impl FnMut(Option<Res<DedicatedServer>>) -> bool + Clone {
+    resource_exists::<DedicatedServer>()
+}
+
+pub fn has_window() -> impl FnMut(Option<Res<DedicatedServer>>) -> bool + Clone {
+    move |res: Option<Res<DedicatedServer>>| res.is_none()
 }
 
 /// Which client id owns this entity?
@@ -187,7 +175,11 @@ fn advance_to_playing_on_connected_and_replication(
     client: Res<RenetClient>,
     mut next_game_state: ResMut<NextState<GameState>>,
     query: Query<With<Replication>>,
+    connection_error: Option<Res<ConnectionError>>,
 ) {
+    if connection_error.is_some() {
+        return;
+    }
     if client.is_connected() && query.iter().count() > 0 {
         next_game_state.set(GameState::Playing);
     }
@@ -0,0 +1,239 @@
+use crate::arena::{Arena, ArenaShape, Force, ZoneWarning};
+use crate::asteroid::LevelAsteroid;
+use crate::game_manager::GameEvent;
+use crate::game_mode::{RoundStatus, Side};
+use crate::health::{Health, KillCause, KillEvent};
+use crate::level::Level;
+use crate::network::clock::ClockSync;
+use crate::network::handshake::ConnectionRejected;
+use crate::network::NetworkOwner;
+use crate::player::prediction::{InputAck, PlayerInput, ShipControls};
+use crate::player::weapons::{Projectile, Weapon};
+use crate::player::{Player, PlayerColor, Thruster};
+use crate::powerup::{Collectible, Debuff, EffectTimers, PowerUp, Shield};
+use crate::respawn::Invulnerable;
+use crate::scoreboard::{MatchLength, PlayerScore, Scoreboard, ScoreboardUpdate};
+use crate::spectator::JoinQueued;
+use bevy::prelude::*;
+use bevy::reflect::{TypeInfo, Typed, VariantInfo};
+use bevy_rapier2d::prelude::Velocity;
+
+/// Shown to players so they can tell which version a server is running.
+pub const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The netcode protocol id.
+///
+/// Changes whenever the crate version or the layout of anything sent over the network changes, so
+/// clients and servers that would desync refuse to connect to each other.
+pub fn protocol_id() -> u64 {
+    let mut hasher = Fnv1a::default();
+    hasher.write(GAME_VERSION);
+    for (name, type_info) in protocol_types() {
+        hasher.write(name);
+        hash_layout(&mut hasher, type_info);
+    }
+    hasher.0
+}
+
+/// Every replicated component and network event, along with the types used in their fields.
+/// The names are hashed instead of [`TypeInfo::type_name`], which changes whenever a type is moved
+/// to another module and isn't guaranteed to be the same between compiler versions.
+/// Keep this in sync with the `app.replicate::<T>()`, `add_server_event` and `add_client_event`
+/// calls, the tests below check that nothing is missing.
+fn protocol_types() -> [(&'static str, &'static TypeInfo); 36] {
+    [
+        // Replicated components
+        ("Transform", Transform::type_info()),
+        ("Velocity", Velocity::type_info()),
+        ("NetworkOwner", NetworkOwner::type_info()),
+        ("Player", Player::type_info()),
+        ("Thruster", Thruster::type_info()),
+        ("Health", Health::type_info()),
+        ("LevelAsteroid", LevelAsteroid::type_info()),
+        ("Arena", Arena::type_info()),
+        ("Force", Force::type_info()),
+        ("ZoneWarning", ZoneWarning::type_info()),
+        ("RoundStatus", RoundStatus::type_info()),
+        ("Invulnerable", Invulnerable::type_info()),
+        ("Weapon", Weapon::type_info()),
+        ("Projectile", Projectile::type_info()),
+        ("PowerUp", PowerUp::type_info()),
+        ("Debuff", Debuff::type_info()),
+        ("Collectible", Collectible::type_info()),
+        ("Shield", Shield::type_info()),
+        ("EffectTimers", EffectTimers::type_info()),
+        ("InputAck", InputAck::type_info()),
+        ("Level", Level::type_info()),
+        // Events
+        ("GameEvent", GameEvent::type_info()),
+        ("PlayerInput", PlayerInput::type_info()),
+        ("KillEvent", KillEvent::type_info()),
+        ("ScoreboardUpdate", ScoreboardUpdate::type_info()),
+        ("JoinQueued", JoinQueued::type_info()),
+        ("ConnectionRejected", ConnectionRejected::type_info()),
+        ("ClockSync", ClockSync::type_info()),
+        // Types used in the fields of the above
+        ("PlayerColor", PlayerColor::type_info()),
+        ("ArenaShape", ArenaShape::type_info()),
+        ("ShipControls", ShipControls::type_info()),
+        ("KillCause", KillCause::type_info()),
+        ("Scoreboard", Scoreboard::type_info()),
+        ("PlayerScore", PlayerScore::type_info()),
+        ("MatchLength", MatchLength::type_info()),
+        ("Side", Side::type_info()),
+    ]
+}
+
+/// Hashes the names of the fields and variants of a type and the types of their fields.
+fn hash_layout(hasher: &mut Fnv1a, type_info: &TypeInfo) {
+    match type_info {
+        TypeInfo::Struct(info) => {
+            for field in info.iter() {
+                hasher.write(field.name());
+                hasher.write(&short_type_name(field.type_name()));
+            }
+        }
+        TypeInfo::TupleStruct(info) => {
+            for field in info.iter() {
+                hasher.write(&short_type_name(field.type_name()));
+            }
+        }
+        TypeInfo::Enum(info) => {
+            for variant in info.iter() {
+                hasher.write(variant.name());
+                match variant {
+                    VariantInfo::Struct(variant) => {
+                        for field in variant.iter() {
+                            hasher.write(field.name());
+                            hasher.write(&short_type_name(field.type_name()));
+                        }
+                    }
+                    VariantInfo::Tuple(variant) => {
+                        for field in variant.iter() {
+                            hasher.write(&short_type_name(field.type_name()));
+                        }
+                    }
+                    VariantInfo::Unit(_) => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Strips the module paths from a type name, e.g. `core::option::Option<crate::powerup::PowerUp>`
+/// becomes `Option<PowerUp>`, so field types hash the same wherever they are defined.
+fn short_type_name(type_name: &str) -> String {
+    let mut short_name = String::with_capacity(type_name.len());
+    let mut path = String::new();
+    for c in type_name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            path.push(c);
+        } else {
+            short_name.push_str(path.rsplit("::").next().unwrap_or_default());
+            path.clear();
+            short_name.push(c);
+        }
+    }
+    short_name.push_str(path.rsplit("::").next().unwrap_or_default());
+    short_name
+}
+
+/// 64 bit FNV-1a.
+/// [`std::hash::Hasher`] implementations aren't guaranteed to be stable between builds, and the
+/// protocol id needs to match between every build of the same version.
+pub(crate) struct Fnv1a(pub(crate) u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    pub(crate) fn write(&mut self, value: &str) {
+        // Separate values so "ab" + "c" hashes differently than "a" + "bc"
+        for byte in value.bytes().chain(std::iter::once(0xff)) {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// The type names used with `call::<T>()` anywhere in the source.
+    fn type_parameters_of(call: &str) -> Vec<String> {
+        let mut sources = Vec::new();
+        read_sources(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("src"),
+            &mut sources,
+        );
+        let pattern = format!("{call}::<");
+        sources
+            .iter()
+            .flat_map(|source| source.match_indices(&pattern).map(|(i, _)| &source[i..]))
+            .filter_map(|call| {
+                let type_name = &call[pattern.len()..call.find('>')?];
+                // Skip the generic definitions of the calls themselves
+                (type_name.len() > 1 && type_name != "ThisType").then(|| type_name.to_string())
+            })
+            .collect()
+    }
+
+    fn read_sources(dir: &Path, sources: &mut Vec<String>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                read_sources(&path, sources);
+            } else if path
+                .extension()
+                .map_or(false, |extension| extension == "rs")
+            {
+                sources.push(std::fs::read_to_string(path).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn every_replicated_type_and_event_is_hashed() {
+        let names = protocol_types().map(|(name, _)| name);
+        let mut found = 0;
+        for call in ["replicate", "add_server_event", "add_client_event"] {
+            let types = type_parameters_of(call);
+            found += types.len();
+            for type_name in types {
+                assert!(
+                    names.contains(&type_name.as_str()),
+                    "{type_name} is sent over the network but missing from protocol_types()"
+                );
+            }
+        }
+        assert!(found > 0, "found no replicated types or events");
+    }
+
+    #[test]
+    fn protocol_type_names_match_their_types() {
+        let types = protocol_types();
+        for (i, (name, type_info)) in types.iter().enumerate() {
+            assert_eq!(*name, short_type_name(type_info.type_name()));
+            assert!(!types[i + 1..].iter().any(|(other, _)| other == name));
+        }
+    }
+
+    #[test]
+    fn short_type_names_drop_module_paths() {
+        assert_eq!(short_type_name("f32"), "f32");
+        assert_eq!(
+            short_type_name("core::option::Option<my_crate::powerup::PowerUp>"),
+            "Option<PowerUp>"
+        );
+        assert_eq!(
+            short_type_name("hashbrown::map::HashMap<a::PlayerColor, b::PlayerScore>"),
+            "HashMap<PlayerColor, PlayerScore>"
+        );
+    }
+}
@@ -1,8 +1,17 @@
+use crate::arena::{Arena, Force, ZoneWarning};
+use crate::game_mode::{ActiveGameMode, Side};
 use crate::health::Health;
+use crate::map::selected_map_definition;
+use crate::network::clock::ServerClock;
+use crate::network::lag_compensation::ViewDelay;
 use crate::network::NetworkOwner;
+use crate::player::prediction::InputAck;
 use crate::player::weapons::Weapon;
 use crate::player::{Player, PlayerAction, PlayerColor, Players, Thruster};
-use bevy::ecs::system::{Command, Spawn};
+use crate::powerup::EffectTimers;
+use crate::respawn::{safest_spawn, Invulnerable};
+use crate::teams::TeamSettings;
+use bevy::ecs::system::Command;
 use bevy::prelude::*;
 use bevy_replicon::prelude::Replication;
 use leafwing_input_manager::action_state::ActionState;
@@ -10,46 +19,68 @@ use leafwing_input_manager::action_state::ActionState;
 pub struct SpawnPlayer {
     pub color: PlayerColor,
     pub network_owner: NetworkOwner,
+    /// Coming back after being destroyed mid round, so spawn away from enemies and be
+    /// [`Invulnerable`] for a moment.
+    pub respawn: bool,
 }
 
-const SPAWN_LOCATIONS: [(Vec2, f32); 6] = [
-    (Vec2::new(440.0, 350.0), 135.0),
-    (Vec2::new(-440.0, -350.0), -45.0),
-    (Vec2::new(440.0, -350.0), 45.0),
-    (Vec2::new(-440.0, 350.0), -135.0),
-    (Vec2::new(0.0, 350.0), 180.0),
-    (Vec2::new(0.0, -350.0), 0.0),
-];
-
 impl Command for SpawnPlayer {
     fn write(self, world: &mut World) {
-        let (position, rotation) = SPAWN_LOCATIONS[self.color as usize];
+        let map = selected_map_definition(world);
+        let team = world
+            .get_resource::<TeamSettings>()
+            .and_then(|teams| teams.team_for(self.color as usize));
+        let spawn = if self.respawn {
+            let side = Side::of(self.color, team.as_ref());
+            let enemies = world
+                .query::<(&Player, Option<&Force>, &Transform)>()
+                .iter(world)
+                .filter(|(player, force, _)| Side::of(player.color, *force) != side)
+                .map(|(_, _, transform)| transform.translation.truncate())
+                .collect::<Vec<_>>();
+            let arena = world.query::<&Arena>().iter(world).next().copied();
+            safest_spawn(&map, arena.as_ref(), &enemies)
+        } else {
+            match world.get_resource::<ActiveGameMode>() {
+                Some(mode) => mode.0.player_spawn(&map, self.color as usize),
+                None => map.player_spawn(self.color as usize),
+            }
+        };
 
         world
             .resource_mut::<Players>()
             .colors
             .insert(self.network_owner.0, self.color);
+        let now = world
+            .resource::<ServerClock>()
+            .now(world.resource::<Time>());
 
-        Spawn {
-            bundle: (
-                Player {
-                    color: self.color,
-                    ..Default::default()
-                },
-                Health::default(),
-                self.network_owner,
-                Replication,
-                Thruster::default(),
-                ActionState::<PlayerAction>::default(),
-                Weapon {
-                    weapon_type: super::weapons::WeaponType::Laser { fire_rate: 1.5 },
-                    ..default()
-                },
-                Transform::from_translation(position.extend(0.0))
-                    .with_rotation(Quat::from_rotation_z(rotation.to_radians())),
-            ),
+        let mut player = world.spawn((
+            Player {
+                color: self.color,
+                ..Default::default()
+            },
+            Health::default(),
+            self.network_owner,
+            Replication,
+            Thruster::default(),
+            InputAck::default(),
+            ViewDelay::default(),
+            ZoneWarning::default(),
+            if self.respawn {
+                Invulnerable::after_respawn(now)
+            } else {
+                Invulnerable::default()
+            },
+            ActionState::<PlayerAction>::default(),
+            Weapon::starting(),
+            EffectTimers::default(),
+            Transform::from_translation(spawn.position.extend(0.0))
+                .with_rotation(Quat::from_rotation_z(spawn.rotation.to_radians())),
+        ));
+        if let Some(team) = team {
+            player.insert(team);
         }
-        .write(world);
 
         world
             .resource_mut::<Players>()
@@ -66,6 +97,7 @@ impl<'w, 's> PlayerCommands for Commands<'w, 's> {
         self.add(SpawnPlayer {
             color,
             network_owner,
+            respawn: false,
         });
     }
 }
@@ -1,22 +1,31 @@
 pub mod commands;
+pub mod prediction;
 pub mod weapons;
 
-use crate::arena::ArenaResident;
+use crate::arena::{Arena, ArenaResident, Force, ZoneWarning};
 use crate::bundles::lyon_rendering::ship_parts::THRUSTER_JET;
 use crate::bundles::lyon_rendering::ship_paths::SHIP_PATH;
 use crate::bundles::lyon_rendering::{get_path_from_verts, LyonRenderBundle};
 use crate::bundles::PhysicsBundle;
-use crate::game_manager::GameState;
-use crate::network::{is_server, NetworkOwner};
+use crate::collision::CollisionDamage;
+use crate::game_manager::{connected_player_count, GameState, MIN_PLAYERS};
+use crate::health::DamageSource;
+use crate::network::clock::ServerClock;
+use crate::network::handshake::{ConnectionRequest, RejectedClients, ValidateConnections};
+use crate::network::{is_client, is_server, DedicatedServer, NetworkOwner};
 use crate::player::commands::PlayerCommands;
+use crate::player::prediction::{LocallyControlled, Prediction, PredictionPlugin, ShipControls};
 use crate::player::weapons::WeaponsPlugin;
 use crate::powerup::{Debuff, PowerUp};
+use crate::spectator::{JoinPolicy, JoinQueued, Spectators};
+use crate::teams::FAVORED_FORCE_ZONE_DAMAGE;
+use bevy::ecs::query::ReadOnlyWorldQuery;
 use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
 use bevy::utils::HashMap;
 use bevy_prototype_lyon::draw::Stroke;
 use bevy_prototype_lyon::prelude::{Fill, ShapeBundle};
-use bevy_rapier2d::prelude::{Damping, Velocity};
+use bevy_rapier2d::prelude::{ActiveEvents, ContactForceEventThreshold, Damping, Velocity};
 use bevy_replicon::prelude::*;
 use bevy_replicon::renet::{RenetClient, ServerEvent};
 use leafwing_input_manager::prelude::*;
@@ -26,12 +35,16 @@ use std::fmt::Formatter;
 
 use self::weapons::DamagedEvent;
 
+pub const PLAYER_LINEAR_DAMPING: f32 = 0.4;
+pub const PLAYER_ANGULAR_DAMPING: f32 = 1.0;
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(InputManagerPlugin::<PlayerAction>::default());
         app.add_plugin(WeaponsPlugin);
+        app.add_plugin(PredictionPlugin);
         app.register_type::<PlayerColor>();
         app.register_type::<Players>();
         app.register_type::<Thruster>();
@@ -40,10 +53,14 @@ impl Plugin for PlayerPlugin {
         app.add_systems(
             (player_actions, damage_players_outside_arena).in_set(OnUpdate(GameState::Playing)),
         );
-        app.add_system(update_thruster.run_if(is_server()));
-        app.add_system(spawn_player_on_connected);
+        app.add_system(update_thruster::<()>.run_if(is_server()));
+        app.add_system(update_thruster::<With<LocallyControlled>>.run_if(is_client()));
+        app.add_system(spawn_player_on_connected.after(ValidateConnections));
         app.add_system(despawn_on_player_disconnect);
-        app.add_systems((pregame_listen_for_player_connect,).in_set(OnUpdate(GameState::PreGame)));
+        app.add_systems(
+            (pregame_listen_for_player_connect.after(spawn_player_on_connected),)
+                .in_set(OnUpdate(GameState::PreGame)),
+        );
         app.add_system(insert_player_bundle);
         app.add_system(handle_thruster);
     }
@@ -55,6 +72,8 @@ pub enum PlayerAction {
     TurnRight,
     Shoot,
     Thrust,
+    /// Use the powerup's ability, if it has one.
+    UseAbility,
 }
 
 impl PlayerAction {
@@ -81,6 +100,9 @@ impl PlayerAction {
         input_map.insert(KeyCode::Space, Self::Shoot);
         input_map.insert(GamepadButtonType::RightTrigger2, Self::Shoot);
         input_map.insert(GamepadButtonType::RightTrigger, Self::Shoot);
+        input_map.insert(KeyCode::LShift, Self::UseAbility);
+        input_map.insert(KeyCode::E, Self::UseAbility);
+        input_map.insert(GamepadButtonType::West, Self::UseAbility);
         input_map
     }
 }
@@ -165,6 +187,11 @@ impl Players {
         self.clients.get(&color).copied()
     }
 
+    /// The colors of every connected player.
+    pub fn colors(&self) -> impl Iterator<Item = PlayerColor> + '_ {
+        self.clients.keys().copied()
+    }
+
     pub fn insert(&mut self, color: PlayerColor, client_id: u64) {
         self.clients.insert(color, client_id);
         self.colors.insert(client_id, color);
@@ -228,8 +255,8 @@ impl PlayerBundle {
             },
             physics: PhysicsBundle {
                 damping: Damping {
-                    linear_damping: 0.4,
-                    angular_damping: 1.0,
+                    linear_damping: PLAYER_LINEAR_DAMPING,
+                    angular_damping: PLAYER_ANGULAR_DAMPING,
                 },
                 ..default()
             },
@@ -293,14 +320,38 @@ fn handle_thruster(
 fn spawn_player_on_connected(
     mut commands: Commands,
     mut events: EventReader<ServerEvent>,
-    players: Res<Players>,
+    mut players: ResMut<Players>,
+    mut spectators: ResMut<Spectators>,
+    rejected_clients: Res<RejectedClients>,
+    game_state: Res<State<GameState>>,
+    join_policy: Res<JoinPolicy>,
+    mut join_queued: EventWriter<ToClients<JoinQueued>>,
 ) {
     for event in events.iter() {
-        if let ServerEvent::ClientConnected(client_id, _) = event {
-            commands.spawn_player(
-                players.available_color().unwrap_or_default(),
-                NetworkOwner(*client_id),
-            );
+        if let ServerEvent::ClientConnected(client_id, user_data) = event {
+            if rejected_clients.contains(*client_id) {
+                continue;
+            }
+            // Anyone joining once every color is taken gets to watch instead
+            let color = players.available_color();
+            let Some(color) =
+                color.filter(|_| !ConnectionRequest::from_user_data(user_data).spectate)
+            else {
+                spectators.insert(*client_id);
+                info!("Client {client_id} connected as a spectator");
+                continue;
+            };
+            if game_state.0 == GameState::Playing && *join_policy == JoinPolicy::NextRound {
+                // Keeping their color gets them a ship from reload_with_current_players
+                players.insert(color, *client_id);
+                join_queued.send(ToClients {
+                    mode: SendMode::Direct(*client_id),
+                    event: JoinQueued,
+                });
+                info!("Player {color} connected mid round, they will join next round");
+                continue;
+            }
+            commands.spawn_player(color, NetworkOwner(*client_id));
 
             info!("Player connected while in play state. Spawning Player")
         }
@@ -311,10 +362,12 @@ fn despawn_on_player_disconnect(
     mut commands: Commands,
     mut events: EventReader<ServerEvent>,
     mut players: ResMut<Players>,
+    mut spectators: ResMut<Spectators>,
     query: Query<(Entity, &Player)>,
 ) {
     for event in events.iter() {
         if let ServerEvent::ClientDisconnected(client_id) = event {
+            spectators.remove(*client_id);
             if let Some(color) = players.color(*client_id) {
                 for (entity, player) in query.iter() {
                     if player.color == color {
@@ -333,9 +386,23 @@ pub fn pregame_listen_for_player_connect(
     mut events: EventReader<ServerEvent>,
     game_state: Res<State<GameState>>,
     mut next_game_state: ResMut<NextState<GameState>>,
+    server: Option<Res<RenetServer>>,
+    dedicated: Option<Res<DedicatedServer>>,
+    spectators: Res<Spectators>,
+    rejected_clients: Res<RejectedClients>,
 ) {
+    let Some(server) = server else {
+        return;
+    };
     for event in events.iter() {
-        if let ServerEvent::ClientConnected(_client_id, _) = event {
+        if let ServerEvent::ClientConnected(client_id, _) = event {
+            if rejected_clients.contains(*client_id) {
+                continue;
+            }
+            if connected_player_count(&server, dedicated.is_some(), &spectators) < MIN_PLAYERS {
+                info!("Player Connected in Pregame, waiting for more players");
+                continue;
+            }
             if game_state.0 != GameState::Playing {
                 next_game_state.set(GameState::Playing);
             }
@@ -345,21 +412,31 @@ pub fn pregame_listen_for_player_connect(
     }
 }
 
+/// The zone gives players a few seconds to get back in, then hurts more the longer they stay out.
 pub fn damage_players_outside_arena(
     server: Option<Res<RenetServer>>,
-    players: Query<(&ArenaResident, Entity), With<Player>>,
+    players: Query<(&ZoneWarning, Option<&Force>, Entity), With<Player>>,
+    arenas: Query<&Arena>,
     mut dmg_events: EventWriter<DamagedEvent>,
+    clock: Res<ServerClock>,
     time: Res<Time>,
 ) {
     if server.is_none() {
         return;
     }
 
-    players.iter().for_each(|(arena_resident, entity)| {
-        if arena_resident.is_outside {
+    let now = clock.now(&time);
+    let favored_force = arenas.get_single().ok().map(|arena| arena.friendly_force);
+    players.iter().for_each(|(zone_warning, force, entity)| {
+        let mut damage_per_second = zone_warning.damage_per_second(now);
+        if force.is_some() && force.copied() == favored_force {
+            damage_per_second *= FAVORED_FORCE_ZONE_DAMAGE;
+        }
+        if damage_per_second > 0.0 {
             dmg_events.send(DamagedEvent {
                 entity,
-                amount: 34.0 * time.delta().as_secs_f32(),
+                amount: damage_per_second * time.delta().as_secs_f32(),
+                source: DamageSource::Arena,
                 normal: None,
                 direction: None,
                 point: None,
@@ -371,10 +448,11 @@ pub fn damage_players_outside_arena(
 /// Handles inserting the player bundle whenever [`Player`] is added to an entity.
 fn insert_player_bundle(
     mut commands: Commands,
-    query: Query<(Entity, &Player, &NetworkOwner, &Transform), Added<Player>>,
+    query: Query<(Entity, &Player, &NetworkOwner, &Transform, Option<&Force>), Added<Player>>,
     client: Option<Res<RenetClient>>,
+    collision_damage: Res<CollisionDamage>,
 ) {
-    for (entity, player, client_id, transform) in query.iter() {
+    for (entity, player, client_id, transform, team) in query.iter() {
         info!("Inserting Player bundle for player: {}", player);
         commands.entity(entity).with_children(|cb| {
             cb.spawn(ThrusterVisualsBundle::with_color(player.color));
@@ -382,10 +460,19 @@ fn insert_player_bundle(
 
         let player_entity = commands
             .entity(entity)
-            .insert((PhysicsBundle::default(),))
+            .insert((
+                PhysicsBundle::default(),
+                // Lets collision damage find out how hard the ship hit something
+                ActiveEvents::CONTACT_FORCE_EVENTS,
+                ContactForceEventThreshold(collision_damage.min_force),
+            ))
             .insert({
                 let mut bundle = PlayerBundle::with_color(player.color);
                 bundle.lyon.shape_render.transform = *transform;
+                // Color ships by team so it's clear who is on whose side
+                if let Some(team) = team {
+                    bundle.lyon.stroke.color = team.color();
+                }
                 bundle
             })
             .id();
@@ -393,21 +480,25 @@ fn insert_player_bundle(
         if let Some(client) = &client {
             // If we are the client this player is for, add an input map
             if client_id.0 == client.client_id() {
-                commands
-                    .entity(player_entity)
-                    .insert(PlayerAction::default_input_map());
+                commands.entity(player_entity).insert((
+                    PlayerAction::default_input_map(),
+                    LocallyControlled,
+                    Prediction::default(),
+                ));
             }
         } else if client_id.0 == SERVER_ID {
             // If we are the server and this player is controlled on the server add an input map
             commands
                 .entity(player_entity)
-                .insert(PlayerAction::default_input_map());
+                .insert((PlayerAction::default_input_map(), LocallyControlled));
         }
     }
 }
 
-pub fn update_thruster(
-    mut query: Query<(&Player, &ActionState<PlayerAction>, &mut Thruster), With<Player>>,
+/// Runs on the server for every player, and on the client for the player it controls so the
+/// thruster doesn't lag behind the input.
+pub fn update_thruster<F: ReadOnlyWorldQuery>(
+    mut query: Query<(&Player, &ActionState<PlayerAction>, &mut Thruster), F>,
     time: Res<Time>,
 ) {
     for (player, action_state, mut thruster) in query.iter_mut() {
@@ -432,17 +523,41 @@ pub fn player_actions(
     time: Res<Time>,
 ) {
     for (player, transform, action_state, mut velocity) in query.iter_mut() {
-        if action_state.pressed(PlayerAction::Thrust) && player.debuff != Some(Debuff::Slowed) {
-            let forward = transform.up();
-            velocity.linvel += forward.xy() * time.delta_seconds() * 50.0;
-        }
+        steer_ship(
+            &action_state.into(),
+            player,
+            transform,
+            &mut velocity,
+            time.delta_seconds(),
+        );
+    }
+}
 
-        if action_state.pressed(PlayerAction::TurnRight) {
-            velocity.angvel -= 7.0 * time.delta_seconds();
-        } else if action_state.pressed(PlayerAction::TurnLeft) {
-            velocity.angvel += 7.0 * time.delta_seconds();
-        } else if velocity.angvel != 0.0 {
-            velocity.angvel = 0.0;
-        }
+/// Applies a single frame of input to a ship.
+/// Shared with client side prediction so the client steers exactly like the server.
+pub fn steer_ship(
+    controls: &ShipControls,
+    player: &Player,
+    transform: &Transform,
+    velocity: &mut Velocity,
+    delta_seconds: f32,
+) {
+    if controls.thrust && player.debuff != Some(Debuff::Slowed) {
+        let forward = transform.up();
+        velocity.linvel += forward.xy() * delta_seconds * 50.0;
+    }
+
+    // Swapping left and right is all it takes to reverse the controls
+    let (turn_left, turn_right) = if player.debuff == Some(Debuff::ReversedControls) {
+        (controls.turn_right, controls.turn_left)
+    } else {
+        (controls.turn_left, controls.turn_right)
+    };
+    if turn_right {
+        velocity.angvel -= 7.0 * delta_seconds;
+    } else if turn_left {
+        velocity.angvel += 7.0 * delta_seconds;
+    } else if velocity.angvel != 0.0 {
+        velocity.angvel = 0.0;
     }
 }
@@ -0,0 +1,394 @@
+use crate::game_manager::GameState;
+use crate::network::interpolation::InterpolationSettings;
+use crate::network::{is_client, is_server, NetworkOwner};
+use crate::player::{player_actions, steer_ship, Player, PlayerAction};
+use crate::player::{PLAYER_ANGULAR_DAMPING, PLAYER_LINEAR_DAMPING};
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::Velocity;
+use bevy_replicon::prelude::*;
+use bevy_replicon::renet::{ChannelConfig, UnreliableChannelConfig};
+use leafwing_input_manager::action_state::ActionState;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// How many inputs the client keeps around waiting for the server to acknowledge them.
+/// At 60 fps this covers a bit over 4 seconds of latency.
+const MAX_INPUT_HISTORY: usize = 256;
+/// Corrections bigger than this are applied instantly instead of smoothed, e.g. after a respawn.
+const SNAP_DISTANCE: f32 = 100.0;
+/// How much of the correction to apply each time the server state arrives.
+const CORRECTION_RATE: f32 = 0.25;
+/// Inputs get their own unreliable channel, after the ones used for replicon's events.
+const INPUT_CHANNEL_ID: u8 = u8::MAX;
+/// Each packet repeats this many of the latest inputs the server hasn't acknowledged, so a lost
+/// packet doesn't lose any input.
+const REDUNDANT_INPUTS: usize = 8;
+/// The size of a [`PlayerInput`] on the wire.
+const ENCODED_INPUT_BYTES: usize = 7;
+
+/// Lets the owning client move its ship right away instead of waiting for the server.
+///
+/// The client sends every frame of input to the server tagged with a sequence number, and keeps
+/// simulating its own ship locally. Input goes over an unreliable channel so a lost packet never
+/// holds up the ones after it, and every packet repeats the latest inputs the server hasn't
+/// acknowledged yet. The server replies with the last sequence it applied in
+/// [`InputAck`] together with the authoritative [`Transform`] and [`Velocity`]. The client then
+/// replays the inputs the server hasn't seen yet on top of that state.
+pub struct PredictionPlugin;
+
+impl Plugin for PredictionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<InputAck>();
+        app.replicate::<InputAck>();
+        app.add_event::<FromClient<PlayerInput>>();
+        app.add_systems(
+            (receive_input_packets, receive_player_input)
+                .chain()
+                .distributive_run_if(is_server())
+                .in_base_set(CoreSet::PreUpdate),
+        );
+        app.add_systems(
+            (reconcile_with_server, send_player_input)
+                .chain()
+                .before(player_actions)
+                .distributive_run_if(is_client())
+                .in_set(OnUpdate(GameState::Playing)),
+        );
+        app.add_system(
+            record_predicted_state
+                .run_if(is_client())
+                .in_base_set(CoreSet::Last),
+        );
+    }
+}
+
+/// The state of the controls for a single frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub struct ShipControls {
+    pub thrust: bool,
+    pub turn_left: bool,
+    pub turn_right: bool,
+    pub shoot: bool,
+    pub use_ability: bool,
+}
+
+impl ShipControls {
+    fn to_bits(self) -> u8 {
+        [
+            self.thrust,
+            self.turn_left,
+            self.turn_right,
+            self.shoot,
+            self.use_ability,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (i, pressed)| bits | (u8::from(*pressed) << i))
+    }
+
+    fn from_bits(bits: u8) -> Self {
+        let pressed = |i: u8| bits & (1 << i) != 0;
+        Self {
+            thrust: pressed(0),
+            turn_left: pressed(1),
+            turn_right: pressed(2),
+            shoot: pressed(3),
+            use_ability: pressed(4),
+        }
+    }
+}
+
+impl From<&ActionState<PlayerAction>> for ShipControls {
+    fn from(action_state: &ActionState<PlayerAction>) -> Self {
+        Self {
+            thrust: action_state.pressed(PlayerAction::Thrust),
+            turn_left: action_state.pressed(PlayerAction::TurnLeft),
+            turn_right: action_state.pressed(PlayerAction::TurnRight),
+            shoot: action_state.pressed(PlayerAction::Shoot),
+            use_ability: action_state.pressed(PlayerAction::UseAbility),
+        }
+    }
+}
+
+/// Sent from the client to the server every frame, on the channel from [`input_channel`].
+/// Packets can be lost or arrive out of order, so the server skips inputs older than the last one
+/// it applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub struct PlayerInput {
+    pub sequence: u32,
+    pub controls: ShipControls,
+    /// How far behind the server we render other entities, used for lag compensation.
+    pub interpolation_delay_millis: u16,
+}
+
+impl PlayerInput {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.sequence.to_le_bytes());
+        bytes.push(self.controls.to_bits());
+        bytes.extend_from_slice(&self.interpolation_delay_millis.to_le_bytes());
+    }
+
+    /// Reads a packet of inputs written with [`PlayerInput::encode`], `None` if it is malformed.
+    fn decode_packet(bytes: &[u8]) -> Option<Vec<PlayerInput>> {
+        if bytes.len() % ENCODED_INPUT_BYTES != 0 {
+            return None;
+        }
+        bytes
+            .chunks_exact(ENCODED_INPUT_BYTES)
+            .map(|input| {
+                Some(PlayerInput {
+                    sequence: u32::from_le_bytes(input[0..4].try_into().ok()?),
+                    controls: ShipControls::from_bits(input[4]),
+                    interpolation_delay_millis: u16::from_le_bytes(input[5..7].try_into().ok()?),
+                })
+            })
+            .collect()
+    }
+}
+
+/// The channel [`PlayerInput`]s are sent on, clients send on it and servers receive on it.
+pub fn input_channel() -> ChannelConfig {
+    ChannelConfig::Unreliable(UnreliableChannelConfig {
+        channel_id: INPUT_CHANNEL_ID,
+        ..Default::default()
+    })
+}
+
+/// The sequence number of the last [`PlayerInput`] the server applied to this player.
+#[derive(Component, Reflect, Default, Copy, Clone, Debug)]
+#[reflect(Component, Default)]
+pub struct InputAck(pub u32);
+
+/// Added to the player that is controlled by this app's input map.
+#[derive(Component, Default, Debug)]
+pub struct LocallyControlled;
+
+/// Only exists on the client, on the player it controls.
+#[derive(Component, Default, Debug)]
+pub struct Prediction {
+    /// Inputs the server hasn't acknowledged yet, with the frame time they were simulated for.
+    history: VecDeque<(PlayerInput, f32)>,
+    /// Where we had predicted the ship to be at the end of the last frame.
+    predicted: Transform,
+}
+
+/// Is `sequence` newer than `other`, accounting for wrap around.
+fn is_newer(sequence: u32, other: u32) -> bool {
+    (sequence.wrapping_sub(other) as i32) > 0
+}
+
+/// Moves the ship the same way the physics engine would, ignoring collisions.
+fn integrate_ship(transform: &mut Transform, velocity: &mut Velocity, delta_seconds: f32) {
+    velocity.linvel *= 1.0 / (1.0 + delta_seconds * PLAYER_LINEAR_DAMPING);
+    velocity.angvel *= 1.0 / (1.0 + delta_seconds * PLAYER_ANGULAR_DAMPING);
+    transform.translation += velocity.linvel.extend(0.0) * delta_seconds;
+    transform.rotate_z(velocity.angvel * delta_seconds);
+}
+
+/// Turns the packets on the input channel into [`PlayerInput`] events, oldest input first.
+fn receive_input_packets(
+    mut server: ResMut<RenetServer>,
+    mut inputs: EventWriter<FromClient<PlayerInput>>,
+) {
+    for client_id in server.clients_id() {
+        while let Some(packet) = server.receive_message(client_id, INPUT_CHANNEL_ID) {
+            let Some(packet_inputs) = PlayerInput::decode_packet(&packet) else {
+                warn!("Dropping malformed input packet from client {client_id}");
+                continue;
+            };
+            for event in packet_inputs {
+                inputs.send(FromClient { client_id, event });
+            }
+        }
+    }
+}
+
+fn receive_player_input(
+    mut inputs: EventReader<FromClient<PlayerInput>>,
+    mut query: Query<(&NetworkOwner, &mut ActionState<PlayerAction>, &mut InputAck)>,
+) {
+    for FromClient { client_id, event } in inputs.iter() {
+        let Some((_, mut action_state, mut ack)) =
+            query.iter_mut().find(|(owner, _, _)| owner.0 == *client_id)
+        else {
+            continue;
+        };
+        // Already applied, either repeated for redundancy or arriving late
+        if !is_newer(event.sequence, ack.0) {
+            continue;
+        }
+        for (action, pressed) in [
+            (PlayerAction::Thrust, event.controls.thrust),
+            (PlayerAction::TurnLeft, event.controls.turn_left),
+            (PlayerAction::TurnRight, event.controls.turn_right),
+            (PlayerAction::Shoot, event.controls.shoot),
+            (PlayerAction::UseAbility, event.controls.use_ability),
+        ] {
+            if pressed && !action_state.pressed(action) {
+                action_state.press(action);
+            } else if !pressed && action_state.pressed(action) {
+                action_state.release(action);
+            }
+        }
+        ack.0 = event.sequence;
+    }
+}
+
+/// Records this frame's input so it can be replayed, and sends it to the server together with the
+/// latest inputs it hasn't acknowledged. [`player_actions`] and the physics step then simulate it
+/// locally.
+///
+/// The sequence keeps counting across ships, so late packets for a destroyed ship are older than
+/// anything sent for the next one.
+fn send_player_input(
+    mut query: Query<(&ActionState<PlayerAction>, &mut Prediction)>,
+    mut client: ResMut<RenetClient>,
+    interpolation: Res<InterpolationSettings>,
+    time: Res<Time>,
+    mut sequence: Local<u32>,
+) {
+    let interpolation_delay_millis = interpolation.delay.as_millis().min(u16::MAX as u128) as u16;
+    for (action_state, mut prediction) in query.iter_mut() {
+        *sequence = sequence.wrapping_add(1);
+        let input = PlayerInput {
+            sequence: *sequence,
+            controls: action_state.into(),
+            interpolation_delay_millis,
+        };
+        if prediction.history.len() >= MAX_INPUT_HISTORY {
+            prediction.history.pop_front();
+        }
+        prediction.history.push_back((input, time.delta_seconds()));
+
+        let unsent = prediction.history.len().saturating_sub(REDUNDANT_INPUTS);
+        let mut packet = Vec::with_capacity(REDUNDANT_INPUTS * ENCODED_INPUT_BYTES);
+        for (input, _) in prediction.history.iter().skip(unsent) {
+            input.encode(&mut packet);
+        }
+        client.send_message(INPUT_CHANNEL_ID, packet);
+    }
+}
+
+/// Runs when new server state for our ship has been replicated.
+/// At this point [`Transform`] and [`Velocity`] hold the server's state as of the acknowledged
+/// input, so replay everything after it to get back to the present.
+fn reconcile_with_server(
+    mut query: Query<
+        (
+            &Player,
+            &InputAck,
+            &mut Prediction,
+            &mut Transform,
+            &mut Velocity,
+        ),
+        Changed<InputAck>,
+    >,
+) {
+    for (player, ack, mut prediction, mut transform, mut velocity) in query.iter_mut() {
+        prediction
+            .history
+            .retain(|(input, _)| is_newer(input.sequence, ack.0));
+
+        let mut replayed_transform = *transform;
+        let mut replayed_velocity = *velocity;
+        for (input, delta_seconds) in prediction.history.iter() {
+            steer_ship(
+                &input.controls,
+                player,
+                &replayed_transform,
+                &mut replayed_velocity,
+                *delta_seconds,
+            );
+            integrate_ship(
+                &mut replayed_transform,
+                &mut replayed_velocity,
+                *delta_seconds,
+            );
+        }
+
+        let error = prediction
+            .predicted
+            .translation
+            .distance(replayed_transform.translation);
+        if error > SNAP_DISTANCE {
+            *transform = replayed_transform;
+        } else {
+            // Ease towards the corrected position so small mispredictions don't make the ship jitter
+            transform.translation = prediction
+                .predicted
+                .translation
+                .lerp(replayed_transform.translation, CORRECTION_RATE);
+            transform.rotation = prediction
+                .predicted
+                .rotation
+                .slerp(replayed_transform.rotation, CORRECTION_RATE);
+        }
+        *velocity = replayed_velocity;
+    }
+}
+
+/// Remembers where the ship ended up after physics, before the next server update overwrites it.
+fn record_predicted_state(mut query: Query<(&Transform, &mut Prediction)>) {
+    for (transform, mut prediction) in query.iter_mut() {
+        prediction.predicted = *transform;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_sequences_are_newer() {
+        assert!(is_newer(2, 1));
+        assert!(!is_newer(1, 2));
+        assert!(!is_newer(5, 5));
+    }
+
+    #[test]
+    fn input_packets_round_trip() {
+        let inputs = [
+            PlayerInput {
+                sequence: 7,
+                controls: ShipControls {
+                    thrust: true,
+                    shoot: true,
+                    ..default()
+                },
+                interpolation_delay_millis: 100,
+            },
+            PlayerInput {
+                sequence: u32::MAX,
+                controls: ShipControls {
+                    turn_left: true,
+                    turn_right: true,
+                    use_ability: true,
+                    ..default()
+                },
+                interpolation_delay_millis: u16::MAX,
+            },
+        ];
+        let mut packet = Vec::new();
+        for input in &inputs {
+            input.encode(&mut packet);
+        }
+        assert_eq!(packet.len(), inputs.len() * ENCODED_INPUT_BYTES);
+        assert_eq!(PlayerInput::decode_packet(&packet).unwrap(), inputs);
+    }
+
+    #[test]
+    fn malformed_input_packets_are_rejected() {
+        let mut packet = Vec::new();
+        PlayerInput::default().encode(&mut packet);
+        packet.pop();
+        assert!(PlayerInput::decode_packet(&packet).is_none());
+        assert_eq!(PlayerInput::decode_packet(&[]), Some(Vec::new()));
+    }
+
+    #[test]
+    fn sequences_are_newer_after_wrapping_around() {
+        assert!(is_newer(0, u32::MAX));
+        assert!(is_newer(3, u32::MAX - 3));
+        assert!(!is_newer(u32::MAX, 0));
+    }
+}
@@ -1,70 +1,248 @@
-use crate::bundles::lyon_rendering::projectile_paths::LASER_PATH;
+use crate::arena::Force;
 use crate::bundles::lyon_rendering::{get_path_from_verts, LyonRenderBundle};
 use crate::game_manager::GameState;
-use crate::network::{is_server, NetworkOwner};
-use crate::player::PlayerAction;
+use crate::health::DamageSource;
+use crate::network::lag_compensation::{cast_ray_at_time, ColliderHistory, Rewind};
+use crate::network::{has_window, is_server, NetworkOwner};
+use crate::player::{Player, PlayerAction};
+use crate::teams::TeamSettings;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
 use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
 use bevy::sprite::Mesh2dHandle;
+use bevy::utils::BoxedFuture;
+use bevy::utils::HashSet;
 use bevy_kira_audio::AudioControl;
 use bevy_prototype_lyon::prelude::*;
 use bevy_prototype_lyon::render::ShapeMaterial;
 use bevy_rapier2d::plugin::RapierContext;
-use bevy_rapier2d::prelude::{ExternalImpulse, QueryFilter};
+use bevy_rapier2d::prelude::{Collider, ExternalImpulse, QueryFilter};
 use bevy_replicon::prelude::{AppReplicationExt, Replication};
 use bevy_replicon::server::ServerSet;
 use leafwing_input_manager::action_state::ActionState;
 use rand::{thread_rng, Rng};
+use serde::Deserialize;
 use std::cmp::Ordering;
-use std::f32::consts::TAU;
+use std::fmt::{Display, Formatter};
 
+/// The weapon ships start with, and go back to when a weapon powerup runs out.
+pub const STARTING_WEAPON: &str = "Laser";
+
+/// Fires projectiles from ships, with the weapons described by the files in `assets/weapons`.
+///
+/// Weapons are `.weapon.ron` files describing a [`WeaponDefinition`].
+/// Changes to them are picked up while the game is running.
 pub struct WeaponsPlugin;
 
 impl Plugin for WeaponsPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<DamagedEvent>();
-        app.register_type::<Laser>();
-        app.register_type::<WeaponType>();
+        app.add_asset::<WeaponDefinition>();
+        app.init_asset_loader::<WeaponLoader>();
+        app.init_resource::<WeaponDefinitions>();
+        app.add_startup_system(load_weapons);
+        app.register_type::<Projectile>();
         app.replicate::<Weapon>();
-        app.replicate::<Laser>();
+        app.replicate::<Projectile>();
         app.add_system(
             fire_weapon_action
                 .run_if(is_server())
                 .in_set(OnUpdate(GameState::Playing)),
         );
-        app.add_system(move_lasers);
-        app.add_system(detect_laser_hits.in_set(ServerSet::Authority));
+        app.add_system(move_projectiles);
+        app.add_system(detect_projectile_hits.in_set(ServerSet::Authority));
         app.add_system(
-            despawn_oldest_if_exceed_count::<30, Laser>
+            despawn_oldest_if_exceed_count::<30, Projectile>
                 .run_if(is_server())
                 .in_base_set(CoreSet::PostUpdate),
         );
         app.add_system(
-            despawn_after_milliseconds::<800, Laser>
+            despawn_expired_projectiles
                 .run_if(is_server())
                 .in_base_set(CoreSet::PostUpdate),
         );
-        app.add_system(spawn_bundle_on_laser_added.in_base_set(CoreSet::PreUpdate));
+        app.add_system(spawn_bundle_on_projectile_added.in_base_set(CoreSet::PreUpdate));
+        app.add_system(
+            play_sound_on_projectile_added
+                .run_if(has_window())
+                .in_base_set(CoreSet::PreUpdate),
+        );
+    }
+}
+
+/// Everything about a weapon and the projectiles it fires.
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "b3c2a5e1-7d4f-4a8e-9c61-2f0e8d94a7b3"]
+pub struct WeaponDefinition {
+    /// Used by [`Weapon`] and [`Projectile`] to refer to this.
+    pub name: String,
+    /// How often this can fire per second.
+    pub fire_rate: f32,
+    /// Projectiles fired with each shot.
+    pub projectile_count: u32,
+    /// How far each projectile can stray from where the ship is pointing, in radians.
+    #[serde(default)]
+    pub spread: f32,
+    /// Units per second.
+    pub speed: f32,
+    /// Seconds before a projectile that hasn't hit anything disappears.
+    pub lifetime: f32,
+    pub damage: f32,
+    /// How hard projectiles push whatever they hit.
+    #[serde(default)]
+    pub knockback: f32,
+    /// Played whenever a projectile is fired, relative to the assets folder.
+    pub sound: Option<String>,
+    /// The line drawn for each projectile, before being scaled by `path_scale`.
+    pub path: Vec<(f32, f32)>,
+    pub path_scale: f32,
+}
+
+impl WeaponDefinition {
+    /// Catches weapons that could never fire or be seen.
+    pub fn validate(&self) -> Result<(), InvalidWeapon> {
+        let problem = if self.fire_rate <= 0.0 {
+            "fire_rate must be more than 0"
+        } else if self.projectile_count == 0 {
+            "projectile_count must be at least 1"
+        } else if self.path.is_empty() {
+            "path needs at least one point"
+        } else {
+            return Ok(());
+        };
+        Err(InvalidWeapon(format!("{}: {problem}", self.name)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidWeapon(String);
+
+impl Display for InvalidWeapon {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidWeapon {}
+
+#[derive(Default)]
+pub struct WeaponLoader;
+
+impl AssetLoader for WeaponLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let weapon = ron::de::from_bytes::<WeaponDefinition>(bytes)?;
+            weapon.validate()?;
+            load_context.set_default_asset(LoadedAsset::new(weapon));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["weapon.ron"]
+    }
+}
+
+/// Handles to every weapon in `assets/weapons`.
+#[derive(Resource, Default)]
+pub struct WeaponDefinitions {
+    handles: Vec<Handle<WeaponDefinition>>,
+}
+
+impl WeaponDefinitions {
+    pub fn get<'a>(
+        &self,
+        assets: &'a Assets<WeaponDefinition>,
+        name: &str,
+    ) -> Option<&'a WeaponDefinition> {
+        self.handles
+            .iter()
+            .filter_map(|handle| assets.get(handle))
+            .find(|weapon| weapon.name == name)
+    }
+
+    /// Like [`WeaponDefinitions::get`], but falls back to the [`STARTING_WEAPON`] for names that
+    /// aren't loaded, warning about each name once. `None` if the starting weapon isn't loaded
+    /// either.
+    pub fn get_or_starting<'a>(
+        &self,
+        assets: &'a Assets<WeaponDefinition>,
+        name: &str,
+        warned: &mut HashSet<String>,
+    ) -> Option<&'a WeaponDefinition> {
+        if let Some(definition) = self.get(assets, name) {
+            return Some(definition);
+        }
+        if warned.insert(name.to_string()) {
+            warn!("No weapon named {name} is loaded, using {STARTING_WEAPON} instead");
+        }
+        self.get(assets, STARTING_WEAPON)
     }
 }
 
-pub fn spawn_bundle_on_laser_added(
+fn load_weapons(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handles = match asset_server.load_folder("weapons") {
+        Ok(handles) => handles
+            .into_iter()
+            .map(|handle| handle.typed::<WeaponDefinition>())
+            .collect(),
+        Err(e) => {
+            error!("Could not load weapons: {e}");
+            Vec::new()
+        }
+    };
+    commands.insert_resource(WeaponDefinitions { handles });
+}
+
+pub fn spawn_bundle_on_projectile_added(
     mut commands: Commands,
-    query: Query<Entity, Added<Laser>>,
-    audio: Res<bevy_kira_audio::Audio>,
-    asset_server: ResMut<AssetServer>,
+    query: Query<(Entity, &Projectile), Added<Projectile>>,
+    definitions: Res<WeaponDefinitions>,
+    assets: Res<Assets<WeaponDefinition>>,
+    mut warned: Local<HashSet<String>>,
 ) {
-    for entity in query.iter() {
+    for (entity, projectile) in query.iter() {
         let Some(mut entcmds) = commands.get_entity(entity) else {
             warn!("Could not find entity to insert bundle into");
             return;
         };
 
-        audio
-            .play(asset_server.load("laserShoot.mp3"))
-            .with_playback_rate(thread_rng().gen_range(0.75..1.125));
+        let Some(definition) =
+            definitions.get_or_starting(&assets, &projectile.weapon, &mut warned)
+        else {
+            continue;
+        };
+        entcmds.insert(ProjectileBundle::new(get_path_from_verts(
+            &definition.path,
+            Vec2::splat(definition.path_scale),
+        )));
+    }
+}
 
-        entcmds.insert(LaserBundle::default());
+pub fn play_sound_on_projectile_added(
+    query: Query<&Projectile, Added<Projectile>>,
+    definitions: Res<WeaponDefinitions>,
+    assets: Res<Assets<WeaponDefinition>>,
+    mut warned: Local<HashSet<String>>,
+    audio: Res<bevy_kira_audio::Audio>,
+    asset_server: ResMut<AssetServer>,
+) {
+    for projectile in query.iter() {
+        let Some(definition) =
+            definitions.get_or_starting(&assets, &projectile.weapon, &mut warned)
+        else {
+            continue;
+        };
+        if let Some(sound) = &definition.sound {
+            audio
+                .play(asset_server.load(sound.as_str()))
+                .with_playback_rate(thread_rng().gen_range(0.75..1.125));
+        }
     }
 }
 
@@ -72,6 +250,7 @@ pub fn spawn_bundle_on_laser_added(
 pub struct DamagedEvent {
     pub entity: Entity,
     pub amount: f32,
+    pub source: DamageSource,
     /// The normal on the surface of this object at the point of impact
     pub normal: Option<Vec2>,
     /// The direction the damage is coming from
@@ -83,166 +262,216 @@ fn fire_weapon_action(
     mut commands: Commands,
     mut query: Query<(
         &mut Weapon,
+        &Player,
         &ActionState<PlayerAction>,
         &GlobalTransform,
         &NetworkOwner,
     )>,
+    definitions: Res<WeaponDefinitions>,
+    assets: Res<Assets<WeaponDefinition>>,
+    mut warned: Local<HashSet<String>>,
     time: Res<Time>,
 ) {
-    for (mut weapon, action_state, transform, owner) in query.iter_mut() {
+    for (mut weapon, player, action_state, transform, owner) in query.iter_mut() {
         if action_state.pressed(PlayerAction::Shoot) {
+            let Some(definition) = definitions.get_or_starting(&assets, &weapon.name, &mut warned)
+            else {
+                continue;
+            };
             weapon.fire(
                 &mut commands,
+                definition,
                 transform.compute_transform(),
                 time.as_ref(),
                 owner,
+                player.debuff.map_or(0.0, |debuff| debuff.aim_spread()),
             );
         }
     }
 }
 
-#[derive(Component, Reflect, Copy, Clone, Debug)]
+#[derive(Component, Reflect, Clone, Debug)]
 #[reflect(Component, Default)]
-pub enum WeaponType {
-    Laser {
-        /// How often this can fire per second
-        fire_rate: f32,
-    },
-    Scattergun {
-        fire_rate: f32,
-        count: u8,
-    },
+pub struct Weapon {
+    /// The [`WeaponDefinition::name`] of the weapon.
+    pub name: String,
+    pub last_fire: f32,
 }
 
-impl Default for WeaponType {
+impl Default for Weapon {
     fn default() -> Self {
-        Self::Laser { fire_rate: 10.0 }
+        Self::starting()
     }
 }
 
-#[derive(Component, Reflect, Default, Copy, Clone, Debug)]
-#[reflect(Component, Default)]
-pub struct Weapon {
-    pub weapon_type: WeaponType,
-    pub last_fire: f32,
-}
-
 impl Weapon {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            last_fire: 0.0,
+        }
+    }
+
+    /// What every ship starts with, and goes back to when a weapon powerup runs out.
+    pub fn starting() -> Self {
+        Self::new(STARTING_WEAPON)
+    }
+
+    /// Fires if the weapon is ready, `spread` is how much further each projectile can stray from
+    /// where the ship is pointing in radians, on top of the weapon's own spread.
     pub fn fire(
         &mut self,
         commands: &mut Commands,
+        definition: &WeaponDefinition,
         transform: Transform,
         time: &Time,
         owner: &NetworkOwner,
+        spread: f32,
     ) {
-        match self.weapon_type {
-            WeaponType::Laser { fire_rate } => {
-                let seconds_between_fire = 1.0 / fire_rate;
-                let time_since_last_fire = time.elapsed_seconds_wrapped() - self.last_fire;
-
-                if time_since_last_fire > seconds_between_fire {
-                    info!("Shoot Laser");
-                    commands.spawn((
-                        Name::new("Laser"),
-                        Replication,
-                        Laser,
-                        *owner,
-                        SpatialBundle::from_transform(transform),
-                        SpawnTime(time.elapsed_seconds_wrapped()),
-                    ));
-                    self.last_fire = time.elapsed_seconds_wrapped();
-                }
-            }
-            WeaponType::Scattergun { fire_rate, count } => {
-                let seconds_between_fire = 1.0 / fire_rate;
-                let time_since_last_fire = time.elapsed_seconds_wrapped() - self.last_fire;
-
-                if time_since_last_fire > seconds_between_fire {
-                    info!("Shooting Scattergun");
-                    let mut rng = thread_rng();
-                    let mut t = transform;
-                    let mut rot;
-
-                    for _ in 0..count {
-                        rot = rng.gen_range(-TAU / 3.0..TAU / 3.0);
-                        t.rotate_z(rot);
-
-                        commands.spawn((
-                            Name::new("Laser"),
-                            Replication,
-                            Laser,
-                            *owner,
-                            SpatialBundle::from_transform(t),
-                            SpawnTime(time.elapsed_seconds_wrapped()),
-                        ));
-
-                        t.rotate_z(-rot);
-                    }
-
-                    self.last_fire = time.elapsed_seconds_wrapped();
-                }
+        let seconds_between_fire = 1.0 / definition.fire_rate;
+        let time_since_last_fire = time.elapsed_seconds_wrapped() - self.last_fire;
+        if time_since_last_fire <= seconds_between_fire {
+            return;
+        }
+
+        info!("Shooting {}", definition.name);
+        let mut rng = thread_rng();
+        let mut stray = |spread: f32| {
+            if spread > 0.0 {
+                rng.gen_range(-spread..spread)
+            } else {
+                0.0
             }
+        };
+        for _ in 0..definition.projectile_count {
+            let mut t = transform;
+            t.rotate_z(stray(definition.spread) + stray(spread));
+            commands.spawn((
+                Name::new(definition.name.clone()),
+                Replication,
+                Projectile::fired_from(definition),
+                *owner,
+                SpatialBundle::from_transform(t),
+                SpawnTime(time.elapsed_seconds_wrapped()),
+            ));
         }
+        self.last_fire = time.elapsed_seconds_wrapped();
     }
 }
 
-#[derive(Component, Reflect, Default, Copy, Clone, Debug)]
+/// Fired by a [`Weapon`], with the stats of the weapon it came from.
+#[derive(Component, Reflect, Default, Clone, Debug)]
 #[reflect(Component, Default)]
-pub struct Laser;
+pub struct Projectile {
+    /// The [`WeaponDefinition::name`] of the weapon that fired this, so clients can draw it and
+    /// play its sound.
+    pub weapon: String,
+    /// Units per second.
+    pub speed: f32,
+    pub damage: f32,
+    /// How hard this pushes whatever it hits.
+    pub knockback: f32,
+    /// Seconds before this disappears if it doesn't hit anything.
+    pub lifetime: f32,
+}
 
-impl Laser {
-    pub const UNITS_PER_SECOND: f32 = 1000.0;
-    pub const DAMAGE: f32 = 50.0;
-    /// How much impulse should be applied to an object the laser hits?
-    pub const DAMAGE_IMPULSE: f32 = 50.0;
+impl Projectile {
+    pub fn fired_from(definition: &WeaponDefinition) -> Self {
+        Self {
+            weapon: definition.name.clone(),
+            speed: definition.speed,
+            damage: definition.damage,
+            knockback: definition.knockback,
+            lifetime: definition.lifetime,
+        }
+    }
 }
 
-fn move_lasers(mut query: Query<&mut Transform, With<Laser>>, time: Res<Time>) {
-    for mut transform in query.iter_mut() {
+fn move_projectiles(mut query: Query<(&mut Transform, &Projectile)>, time: Res<Time>) {
+    for (mut transform, projectile) in query.iter_mut() {
         let forward = transform.up();
-        transform.translation += forward * time.delta_seconds() * Laser::UNITS_PER_SECOND;
+        transform.translation += forward * time.delta_seconds() * projectile.speed;
     }
 }
 
-fn detect_laser_hits(
+fn detect_projectile_hits(
     mut commands: Commands,
-    query: Query<(Entity, &GlobalTransform, &NetworkOwner), With<Laser>>,
+    query: Query<(
+        Entity,
+        &Projectile,
+        &GlobalTransform,
+        &NetworkOwner,
+        Option<&Rewind>,
+    )>,
     rapier_context: Res<RapierContext>,
+    histories: Query<(Entity, &Collider, &ColliderHistory)>,
     time: Res<Time>,
     network_owners: Query<&NetworkOwner>,
+    ships: Query<(Entity, &NetworkOwner), With<Player>>,
+    teams: Query<(&NetworkOwner, &Force), With<Player>>,
+    team_settings: Res<TeamSettings>,
     mut damaged_events: EventWriter<DamagedEvent>,
     mut impulses: Query<&mut ExternalImpulse>,
 ) {
-    for (laser_entity, transform, owner) in query.iter() {
-        // Start from where the laser would have been the last frame
+    for (projectile_entity, projectile, transform, owner, rewind) in query.iter() {
+        // Start from where the projectile would have been the last frame
         let ray_start = transform.translation().xy()
-            - (transform.down().xy() * time.delta_seconds() * Laser::UNITS_PER_SECOND);
-        if let Some((hit_entity, intersection)) = rapier_context.cast_ray_and_get_normal(
-            ray_start,
-            transform.up().xy(),
-            Laser::UNITS_PER_SECOND * time.delta_seconds(),
-            true,
-            QueryFilter::default().exclude_sensors(),
-        ) {
-            if network_owners.get(hit_entity) != Ok(owner) {
+            - (transform.down().xy() * time.delta_seconds() * projectile.speed);
+        let ray_length = projectile.speed * time.delta_seconds();
+        let shooter = ships
+            .iter()
+            .find(|(_, ship_owner)| *ship_owner == owner)
+            .map(|(ship, _)| ship);
+        let hit = match rewind {
+            // Check against the world the shooter was looking at when they fired
+            Some(rewind) => cast_ray_at_time(
+                &rapier_context,
+                &histories,
+                time.elapsed_seconds() - rewind.0,
+                ray_start,
+                transform.up().xy(),
+                ray_length,
+                shooter,
+            ),
+            None => rapier_context.cast_ray_and_get_normal(
+                ray_start,
+                transform.up().xy(),
+                ray_length,
+                true,
+                QueryFilter::default()
+                    .exclude_sensors()
+                    .predicate(&|entity| Some(entity) != shooter),
+            ),
+        };
+        if let Some((hit_entity, intersection)) = hit {
+            let shooter_team = teams
+                .iter()
+                .find(|(team_owner, _)| *team_owner == owner)
+                .map(|(_, team)| team);
+            let hit_team = teams.get(hit_entity).ok().map(|(_, team)| team);
+            // Without friendly fire, projectiles pass through teammates
+            if network_owners.get(hit_entity) != Ok(owner)
+                && team_settings.can_damage(shooter_team, hit_team)
+            {
                 damaged_events.send(DamagedEvent {
                     entity: hit_entity,
-                    amount: Laser::DAMAGE,
+                    amount: projectile.damage,
+                    source: DamageSource::Player(*owner),
                     normal: Some(intersection.normal),
                     direction: Some(transform.up().xy()),
                     point: Some(intersection.point),
                 });
                 if let Ok(mut impulse) = impulses.get_mut(hit_entity) {
-                    impulse.impulse += transform.up().xy() * Laser::DAMAGE_IMPULSE;
+                    impulse.impulse += transform.up().xy() * projectile.knockback;
                 }
-                commands.entity(laser_entity).despawn_recursive();
+                commands.entity(projectile_entity).despawn_recursive();
             }
         }
     }
 }
 
 #[derive(Bundle)]
-pub struct LaserBundle {
+pub struct ProjectileBundle {
     pub path: Path,
     pub mesh: Mesh2dHandle,
     pub material: Handle<ShapeMaterial>,
@@ -253,13 +482,10 @@ pub struct LaserBundle {
     pub global_transform: GlobalTransform,
 }
 
-impl Default for LaserBundle {
-    fn default() -> Self {
+impl ProjectileBundle {
+    pub fn new(path: Path) -> Self {
         let lyon = LyonRenderBundle {
-            shape_render: ShapeBundle {
-                path: get_path_from_verts(&LASER_PATH, Vec2::splat(2.0)),
-                ..default()
-            },
+            shape_render: ShapeBundle { path, ..default() },
             ..default()
         };
         Self {
@@ -304,14 +530,83 @@ fn despawn_oldest_if_exceed_count<const MAX: usize, C: Component>(
     }
 }
 
-fn despawn_after_milliseconds<const MILLISECONDS: usize, C: Component>(
+fn despawn_expired_projectiles(
     mut commands: Commands,
-    query: Query<(Entity, &SpawnTime), With<C>>,
+    query: Query<(Entity, &SpawnTime, &Projectile)>,
     time: Res<Time>,
 ) {
-    for (entity, spawn_time) in query.iter() {
-        if ((time.elapsed_seconds_wrapped() - spawn_time.0) * 1000.0) as usize > MILLISECONDS {
+    for (entity, spawn_time, projectile) in query.iter() {
+        if time.elapsed_seconds_wrapped() - spawn_time.0 > projectile.lifetime {
             commands.entity(entity).despawn_recursive();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::powerup::PowerUp;
+
+    fn weapon_files() -> Vec<WeaponDefinition> {
+        std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/weapons"))
+            .unwrap()
+            .map(|entry| {
+                let bytes = std::fs::read(entry.unwrap().path()).unwrap();
+                ron::de::from_bytes::<WeaponDefinition>(&bytes).unwrap()
+            })
+            .collect()
+    }
+
+    fn laser() -> WeaponDefinition {
+        weapon_files()
+            .into_iter()
+            .find(|weapon| weapon.name == STARTING_WEAPON)
+            .unwrap()
+    }
+
+    #[test]
+    fn weapon_files_are_valid() {
+        for weapon in weapon_files() {
+            assert!(weapon.validate().is_ok(), "{} is not valid", weapon.name);
+        }
+    }
+
+    #[test]
+    fn weapons_in_code_have_files() {
+        let names: Vec<String> = weapon_files()
+            .into_iter()
+            .map(|weapon| weapon.name)
+            .collect();
+        let powerups = [
+            PowerUp::HitherThither,
+            PowerUp::Scattergun,
+            PowerUp::RapidFire,
+            PowerUp::Shield,
+        ];
+        for name in powerups
+            .iter()
+            .filter_map(PowerUp::weapon)
+            .chain([STARTING_WEAPON])
+        {
+            assert!(names.iter().any(|file| file == name), "No file for {name}");
+        }
+    }
+
+    #[test]
+    fn weapons_that_cant_fire_are_invalid() {
+        let mut weapon = laser();
+        weapon.fire_rate = 0.0;
+        assert!(weapon.validate().is_err());
+
+        let mut weapon = laser();
+        weapon.projectile_count = 0;
+        assert!(weapon.validate().is_err());
+    }
+
+    #[test]
+    fn weapons_without_a_path_are_invalid() {
+        let mut weapon = laser();
+        weapon.path.clear();
+        assert!(weapon.validate().is_err());
+    }
+}
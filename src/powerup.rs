@@ -1,26 +1,55 @@
 use bevy::prelude::*;
-use bevy_prototype_lyon::prelude::ShapeBundle;
+use bevy_prototype_lyon::prelude::{ShapeBundle, Stroke};
 use bevy_rapier2d::prelude::{Collider, QueryFilter, RapierContext, Sensor};
 use bevy_replicon::replication_core::{AppReplicationExt, Replication};
+use leafwing_input_manager::action_state::ActionState;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
 
 use crate::{
+    arena::Arena,
+    asteroid::Asteroid,
     bundles::{
         lyon_rendering::{
-            get_path_from_verts,
-            powerups::{RAPIDFIRE_PATH, SCATTERGUN_PATH},
+            get_circle_path, get_path_from_verts,
+            powerups::{
+                HEALTH_BURN_PATH, HITHER_THITHER_PATH, INACCURACY_PATH, RAPIDFIRE_PATH,
+                REVERSED_CONTROLS_PATH, SCATTERGUN_PATH, SHIELD_PATH, SLOWED_PATH,
+            },
             LyonRenderBundle,
         },
         PhysicsBundle,
     },
     game_manager::GameState,
-    network::is_server,
+    health::DamageSource,
+    network::{clock::ServerClock, has_window, is_server},
     player::{
-        weapons::{Weapon, WeaponType},
-        Player,
+        weapons::{DamagedEvent, Weapon},
+        Player, PlayerAction,
     },
 };
 
-#[derive(Component, Reflect, Copy, Default, FromReflect, Debug, Clone)]
+/// How much damage a fresh shield soaks up.
+pub const SHIELD_STRENGTH: f32 = 75.0;
+/// Radius of the ring drawn around shielded ships.
+const SHIELD_RING_RADIUS: f32 = 26.0;
+/// How far [`PowerUp::HitherThither`] jumps the ship forward.
+pub const TELEPORT_DISTANCE: f32 = 200.0;
+/// When the full jump is blocked, it is tried this many times at shorter distances.
+const TELEPORT_STEPS: u32 = 4;
+/// Seconds between teleports.
+pub const TELEPORT_COOLDOWN: f32 = 3.0;
+/// Damage per second from [`Debuff::HealthBurn`].
+pub const HEALTH_BURN_DAMAGE: f32 = 3.0;
+/// How far lasers can stray from where the ship is pointing with [`Debuff::Inaccuracy`], in
+/// radians.
+pub const INACCURACY_SPREAD: f32 = 0.3;
+/// Picking up the debuff a ship already has adds to its time, up to this many times its duration.
+pub const MAX_DEBUFF_STACKS: f32 = 2.0;
+/// How many random spots are tried when looking for somewhere clear to spawn a powerup.
+const SPAWN_ATTEMPTS: usize = 20;
+
+#[derive(Component, Reflect, Copy, Default, FromReflect, Debug, Clone, Eq, PartialEq)]
 #[reflect(Component, Default)]
 pub enum PowerUp {
     HitherThither,
@@ -40,11 +69,209 @@ pub enum Debuff {
     ReversedControls,
 }
 
+impl PowerUp {
+    /// Seconds the powerup lasts after being picked up.
+    pub fn duration(&self) -> f32 {
+        match self {
+            PowerUp::HitherThither => 15.0,
+            PowerUp::Scattergun => 12.0,
+            PowerUp::RapidFire => 8.0,
+            PowerUp::Shield => 20.0,
+        }
+    }
+
+    /// The name of the [`WeaponDefinition`](crate::player::weapons::WeaponDefinition) the powerup
+    /// swaps in, if it changes weapons.
+    pub fn weapon(&self) -> Option<&'static str> {
+        match self {
+            PowerUp::Scattergun => Some("Scattergun"),
+            PowerUp::RapidFire => Some("Rapid Fire"),
+            PowerUp::HitherThither | PowerUp::Shield => None,
+        }
+    }
+
+    pub fn icon(&self) -> &'static [(f32, f32)] {
+        match self {
+            PowerUp::HitherThither => &HITHER_THITHER_PATH,
+            PowerUp::Scattergun => &SCATTERGUN_PATH,
+            PowerUp::RapidFire => &RAPIDFIRE_PATH,
+            PowerUp::Shield => &SHIELD_PATH,
+        }
+    }
+}
+
+impl Debuff {
+    /// Seconds the debuff lasts after being picked up.
+    pub fn duration(&self) -> f32 {
+        match self {
+            Debuff::Slowed => 6.0,
+            Debuff::Inaccuracy => 8.0,
+            Debuff::HealthBurn => 10.0,
+            Debuff::ReversedControls => 5.0,
+        }
+    }
+
+    /// How far this makes lasers stray from where the ship is pointing, in radians.
+    pub fn aim_spread(&self) -> f32 {
+        match self {
+            Debuff::Inaccuracy => INACCURACY_SPREAD,
+            Debuff::Slowed | Debuff::HealthBurn | Debuff::ReversedControls => 0.0,
+        }
+    }
+
+    pub fn icon(&self) -> &'static [(f32, f32)] {
+        match self {
+            Debuff::Slowed => &SLOWED_PATH,
+            Debuff::Inaccuracy => &INACCURACY_PATH,
+            Debuff::HealthBurn => &HEALTH_BURN_PATH,
+            Debuff::ReversedControls => &REVERSED_CONTROLS_PATH,
+        }
+    }
+}
+
 #[derive(Component, Default, Reflect)]
 #[reflect(Component, Default)]
 
 pub struct Collectible;
 
+/// How the server keeps the arena stocked with powerups during a round.
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct PowerupSpawner {
+    /// Seconds between new powerups.
+    pub interval_seconds: f32,
+    /// No new powerups spawn while this many are waiting to be picked up.
+    pub max_pickups: usize,
+    /// How far new powerups have to be from ships, other powerups and the edge of asteroids.
+    pub clearance: f32,
+    /// What can spawn and how often.
+    pub table: Vec<PowerupWeight>,
+}
+
+/// An entry in the [`PowerupSpawner`] table. A pair with twice the weight of another spawns twice
+/// as often.
+#[derive(Reflect, FromReflect, Debug, Copy, Clone)]
+pub struct PowerupWeight {
+    pub powerup: PowerUp,
+    pub debuff: Debuff,
+    pub weight: f32,
+}
+
+impl PowerupWeight {
+    const fn new(powerup: PowerUp, debuff: Debuff, weight: f32) -> Self {
+        Self {
+            powerup,
+            debuff,
+            weight,
+        }
+    }
+}
+
+/// What a level starts with, and what [`PowerupSpawner`] starts out spawning.
+///
+/// Levels always use this rather than the spawner's table, which can be changed while the game is
+/// running, so the same seed always builds the same level.
+pub const LEVEL_POWERUPS: [PowerupWeight; 4] = [
+    PowerupWeight::new(PowerUp::RapidFire, Debuff::Slowed, 1.0),
+    PowerupWeight::new(PowerUp::Scattergun, Debuff::Inaccuracy, 1.0),
+    PowerupWeight::new(PowerUp::Shield, Debuff::HealthBurn, 1.0),
+    PowerupWeight::new(PowerUp::HitherThither, Debuff::ReversedControls, 1.0),
+];
+
+/// Picks a pair from a table, `None` if the table is empty or has no weight.
+pub fn pick_powerup<R: Rng + ?Sized>(
+    table: &[PowerupWeight],
+    rng: &mut R,
+) -> Option<(PowerUp, Debuff)> {
+    table
+        .choose_weighted(rng, |entry| entry.weight)
+        .ok()
+        .map(|entry| (entry.powerup, entry.debuff))
+}
+
+impl Default for PowerupSpawner {
+    fn default() -> Self {
+        Self {
+            interval_seconds: 15.0,
+            max_pickups: 4,
+            clearance: 60.0,
+            table: LEVEL_POWERUPS.to_vec(),
+        }
+    }
+}
+
+impl PowerupSpawner {
+    /// Picks a pair from the table, `None` if the table is empty or has no weight.
+    pub fn pick<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<(PowerUp, Debuff)> {
+        pick_powerup(&self.table, rng)
+    }
+}
+
+/// Counts down to the next powerup spawn, only used on the server.
+#[derive(Resource, Default, Debug)]
+pub struct PowerupSpawnTimer(Timer);
+
+/// Soaks up damage before it reaches the ship's health, from [`PowerUp::Shield`].
+/// Damage from [`Debuff::HealthBurn`] goes straight through.
+/// Replicated so clients can draw the ring around the ship.
+#[derive(Component, Reflect, Default, Copy, Clone, Debug, PartialEq)]
+#[reflect(Component, Default)]
+pub struct Shield {
+    pub strength: f32,
+}
+
+impl Shield {
+    pub fn full() -> Self {
+        Self {
+            strength: SHIELD_STRENGTH,
+        }
+    }
+
+    /// Takes as much of the damage as the shield has left, returns the damage that gets through.
+    pub fn absorb(&mut self, amount: f32) -> f32 {
+        let absorbed = amount.min(self.strength);
+        self.strength -= absorbed;
+        amount - absorbed
+    }
+}
+
+/// The ring drawn around a ship with a [`Shield`].
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct ShieldRing;
+
+/// When the ship's [`Player::powerup`] and [`Player::debuff`] run out, from the [`ServerClock`].
+/// Replicated so clients can show the countdowns, only changes when an effect is picked up.
+#[derive(Component, Reflect, Default, Copy, Clone, Debug, PartialEq)]
+#[reflect(Component, Default)]
+pub struct EffectTimers {
+    pub powerup_ends_at: f32,
+    pub debuff_ends_at: f32,
+}
+
+impl EffectTimers {
+    pub fn powerup_seconds_left(&self, now: f32) -> f32 {
+        (self.powerup_ends_at - now).max(0.0)
+    }
+
+    pub fn debuff_seconds_left(&self, now: f32) -> f32 {
+        (self.debuff_ends_at - now).max(0.0)
+    }
+
+    /// Adds the debuff's duration to what is left of the same debuff, up to [`MAX_DEBUFF_STACKS`]
+    /// times its duration.
+    fn stack_debuff(&mut self, debuff: Debuff, now: f32) {
+        let seconds_left = self.debuff_seconds_left(now) + debuff.duration();
+        self.debuff_ends_at = now + seconds_left.min(debuff.duration() * MAX_DEBUFF_STACKS);
+    }
+}
+
+/// When [`PowerUp::HitherThither`] can be used again, only exists on the server.
+#[derive(Component, Default, Debug)]
+pub struct TeleportCooldown {
+    ready_at: f32,
+}
+
 #[derive(Bundle, Default)]
 pub struct PowerUpServerBundle {
     pub spatial: SpatialBundle,
@@ -83,20 +310,15 @@ pub fn spawn_powerup(
 
 pub fn spawn_client_powerup(
     mut cmds: Commands,
-    added: Query<(Entity, &Transform, &PowerUp), Added<Collectible>>,
+    added: Query<(Entity, &Transform, &PowerUp, &Debuff), Added<Collectible>>,
 ) {
-    added.iter().for_each(|(ent, transform, powerup)| {
+    added.iter().for_each(|(ent, transform, powerup, debuff)| {
         info!("Spawning Client Powerup");
-        let path = match powerup {
-            PowerUp::RapidFire => RAPIDFIRE_PATH.to_vec(),
-            PowerUp::Scattergun => SCATTERGUN_PATH.to_vec(),
-            _ => RAPIDFIRE_PATH.to_vec(),
-        };
         cmds.entity(ent)
             .insert(PowerUpClientBundle {
                 shape_bundle: LyonRenderBundle {
                     shape_render: ShapeBundle {
-                        path: get_path_from_verts(path.as_ref(), Vec2::splat(16.)),
+                        path: get_path_from_verts(powerup.icon(), Vec2::splat(16.)),
                         transform: *transform,
                         ..Default::default()
                     },
@@ -104,16 +326,39 @@ pub fn spawn_client_powerup(
                 },
                 ..Default::default()
             })
-            .insert(Name::new("Powerup"));
+            .insert(Name::new("Powerup"))
+            .with_children(|parent| {
+                // The catch that comes with the powerup
+                parent.spawn(LyonRenderBundle {
+                    shape_render: ShapeBundle {
+                        path: get_path_from_verts(debuff.icon(), Vec2::splat(8.)),
+                        transform: Transform::from_xyz(12.0, -12.0, 0.0),
+                        ..Default::default()
+                    },
+                    stroke: Stroke::new(Color::RED, 1.5),
+                    ..Default::default()
+                });
+            });
     });
 }
 
+/// Gives the powerup and debuff to the first ship touching them.
+///
+/// A ship only holds one powerup and one debuff at a time:
+/// - Picking up the powerup it already has refreshes its time, and refills the shield.
+/// - Picking up a different powerup ends the current one and starts the new one.
+/// - Picking up the debuff it already has adds to its time, up to [`MAX_DEBUFF_STACKS`] times its
+///   duration.
+/// - Picking up a different debuff replaces the current one.
 pub fn collect_powerups(
     rapier_context: Res<RapierContext>,
     powerups: Query<(&PowerUp, &Debuff, &Collider, &Transform, Entity), Without<Player>>,
-    mut players: Query<(Entity, &mut Player, &mut Weapon), With<Player>>,
+    mut players: Query<(&mut Player, &mut Weapon, &mut EffectTimers)>,
     mut cmds: Commands,
+    clock: Res<ServerClock>,
+    time: Res<Time>,
 ) {
+    let now = clock.now(&time);
     powerups
         .iter()
         .for_each(|(powerup, debuff, collider, transform, powerup_ent)| {
@@ -124,29 +369,36 @@ pub fn collect_powerups(
                 collider,
                 QueryFilter::default(),
                 |e| {
-                    if let Ok((_entity, mut player, mut weapon)) = players.get_mut(e) {
+                    if let Ok((mut player, mut weapon, mut timers)) = players.get_mut(e) {
+                        let refreshed = player.powerup == Some(*powerup);
+                        if player.powerup.is_some() && !refreshed {
+                            end_powerup(&mut cmds, e, &mut player, &mut weapon);
+                        }
                         player.powerup = Some(*powerup);
-                        player.debuff = Some(*debuff);
+                        timers.powerup_ends_at = now + powerup.duration();
 
+                        if let Some(name) = powerup.weapon() {
+                            *weapon = Weapon::new(name);
+                        }
                         match powerup {
-                            PowerUp::Scattergun => {
-                                *weapon = Weapon {
-                                    weapon_type: WeaponType::Scattergun {
-                                        fire_rate: 1.5,
-                                        count: 7,
-                                    },
-                                    ..default()
-                                }
+                            PowerUp::Shield => {
+                                cmds.entity(e).insert(Shield::full());
                             }
-                            PowerUp::RapidFire => {
-                                *weapon = Weapon {
-                                    weapon_type: WeaponType::Laser { fire_rate: 10.0 },
-                                    ..Default::default()
-                                };
+                            // Picking it up again shouldn't let the ship skip its cooldown
+                            PowerUp::HitherThither if !refreshed => {
+                                cmds.entity(e).insert(TeleportCooldown::default());
                             }
-                            _ => {}
+                            PowerUp::HitherThither => {}
+                            PowerUp::Scattergun | PowerUp::RapidFire => {}
                         }
 
+                        if player.debuff == Some(*debuff) {
+                            timers.stack_debuff(*debuff, now);
+                        } else {
+                            timers.debuff_ends_at = now + debuff.duration();
+                        }
+                        player.debuff = Some(*debuff);
+
                         cmds.entity(powerup_ent).despawn_recursive();
                         return false;
                     }
@@ -156,6 +408,214 @@ pub fn collect_powerups(
         });
 }
 
+/// Takes away what the ship's current powerup gave it.
+fn end_powerup(cmds: &mut Commands, entity: Entity, player: &mut Player, weapon: &mut Weapon) {
+    match player.powerup.take() {
+        Some(PowerUp::Scattergun | PowerUp::RapidFire) => *weapon = Weapon::starting(),
+        Some(PowerUp::Shield) => {
+            cmds.entity(entity).insert(Shield::default());
+        }
+        Some(PowerUp::HitherThither) => {
+            cmds.entity(entity).remove::<TeleportCooldown>();
+        }
+        None => {}
+    }
+}
+
+/// Counts down powerups and debuffs, ending them when their time is up.
+fn expire_effects(
+    mut cmds: Commands,
+    mut players: Query<(Entity, &mut Player, &mut Weapon, &EffectTimers)>,
+    clock: Res<ServerClock>,
+    time: Res<Time>,
+) {
+    let now = clock.now(&time);
+    for (entity, mut player, mut weapon, timers) in players.iter_mut() {
+        if player.powerup.is_some() && timers.powerup_ends_at <= now {
+            end_powerup(&mut cmds, entity, &mut player, &mut weapon);
+        }
+        if player.debuff.is_some() && timers.debuff_ends_at <= now {
+            player.debuff = None;
+        }
+    }
+}
+
+fn reset_powerup_spawner(mut cmds: Commands, spawner: Res<PowerupSpawner>) {
+    cmds.insert_resource(PowerupSpawnTimer(Timer::from_seconds(
+        spawner.interval_seconds,
+        TimerMode::Repeating,
+    )));
+}
+
+/// Spawns a powerup from the [`PowerupSpawner`] table every so often, somewhere inside the arena
+/// that is clear of asteroids, ships and other powerups.
+fn spawn_powerups_over_time(
+    mut cmds: Commands,
+    spawner: Res<PowerupSpawner>,
+    mut timer: ResMut<PowerupSpawnTimer>,
+    arenas: Query<&Arena>,
+    asteroids: Query<(&Transform, &Asteroid)>,
+    obstacles: Query<&Transform, Or<(With<Player>, With<Collectible>)>>,
+    pickups: Query<(), With<Collectible>>,
+    time: Res<Time>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() || pickups.iter().count() >= spawner.max_pickups
+    {
+        return;
+    }
+    let Ok(arena) = arenas.get_single() else {
+        return;
+    };
+
+    let mut rng = thread_rng();
+    let is_clear = |point: Vec2| {
+        asteroids.iter().all(|(transform, asteroid)| {
+            transform.translation.truncate().distance(point) >= spawner.clearance + asteroid.scale
+        }) && obstacles
+            .iter()
+            .all(|transform| transform.translation.truncate().distance(point) >= spawner.clearance)
+    };
+    let half_size = arena.current_size / 2.0;
+    let position = (0..SPAWN_ATTEMPTS)
+        .map(|_| {
+            arena.current_center
+                + Vec2::new(
+                    rng.gen_range(-half_size.x..=half_size.x),
+                    rng.gen_range(-half_size.y..=half_size.y),
+                )
+        })
+        .find(|point| arena.contains(*point) && is_clear(*point));
+    // Try again next time rather than spawn inside something
+    let Some(position) = position else {
+        return;
+    };
+    if let Some((powerup, debuff)) = spawner.pick(&mut rng) {
+        spawn_powerup(
+            &mut cmds,
+            Transform::from_translation(position.extend(0.2)),
+            powerup,
+            debuff,
+        );
+    }
+}
+
+/// Jumps ships holding [`PowerUp::HitherThither`] forward when they use their ability.
+///
+/// The ship lands at the furthest of [`TELEPORT_STEPS`] distances up to [`TELEPORT_DISTANCE`] that
+/// is inside the arena and doesn't overlap anything solid. If none are clear, the ship stays put
+/// and the cooldown isn't used.
+fn teleport_players(
+    rapier_context: Res<RapierContext>,
+    mut players: Query<(
+        Entity,
+        &Player,
+        &ActionState<PlayerAction>,
+        &Collider,
+        &mut Transform,
+        &mut TeleportCooldown,
+    )>,
+    arenas: Query<&Arena>,
+    time: Res<Time>,
+) {
+    let Ok(arena) = arenas.get_single() else {
+        return;
+    };
+    for (entity, player, action_state, collider, mut transform, mut cooldown) in players.iter_mut()
+    {
+        if player.powerup != Some(PowerUp::HitherThither)
+            || !action_state.pressed(PlayerAction::UseAbility)
+            || cooldown.ready_at > time.elapsed_seconds()
+        {
+            continue;
+        }
+        let origin = transform.translation.truncate();
+        let forward = transform.up().truncate();
+        let rotation = transform.rotation.to_euler(EulerRot::XYZ).2;
+        let filter = QueryFilter::default()
+            .exclude_sensors()
+            .exclude_collider(entity);
+        let target = (0..TELEPORT_STEPS)
+            .map(|step| {
+                origin
+                    + forward * TELEPORT_DISTANCE * (TELEPORT_STEPS - step) as f32
+                        / TELEPORT_STEPS as f32
+            })
+            .find(|&target| {
+                if !arena.contains(target) {
+                    return false;
+                }
+                let mut blocked = false;
+                rapier_context.intersections_with_shape(target, rotation, collider, filter, |_| {
+                    blocked = true;
+                    false
+                });
+                !blocked
+            });
+        let Some(target) = target else {
+            continue;
+        };
+        transform.translation = target.extend(transform.translation.z);
+        cooldown.ready_at = time.elapsed_seconds() + TELEPORT_COOLDOWN;
+    }
+}
+
+fn burn_players(
+    players: Query<(Entity, &Player)>,
+    mut damaged_events: EventWriter<DamagedEvent>,
+    time: Res<Time>,
+) {
+    for (entity, player) in players.iter() {
+        if player.debuff == Some(Debuff::HealthBurn) {
+            damaged_events.send(DamagedEvent {
+                entity,
+                amount: HEALTH_BURN_DAMAGE * time.delta_seconds(),
+                source: DamageSource::HealthBurn,
+                normal: None,
+                direction: None,
+                point: None,
+            });
+        }
+    }
+}
+
+fn spawn_shield_rings(mut cmds: Commands, shields: Query<Entity, Added<Shield>>) {
+    for entity in shields.iter() {
+        let ring = cmds
+            .spawn((
+                ShieldRing,
+                Name::new("Shield"),
+                LyonRenderBundle {
+                    shape_render: ShapeBundle {
+                        path: get_circle_path(SHIELD_RING_RADIUS),
+                        ..Default::default()
+                    },
+                    stroke: Stroke::new(Color::CYAN, 1.5),
+                    ..Default::default()
+                },
+            ))
+            .id();
+        cmds.entity(entity).add_child(ring);
+    }
+}
+
+/// Fades the ring as the shield wears down, and hides it once the shield is gone.
+fn update_shield_rings(
+    shields: Query<(&Shield, &Children), Changed<Shield>>,
+    mut rings: Query<(&mut Stroke, &mut Visibility), With<ShieldRing>>,
+) {
+    for (shield, children) in shields.iter() {
+        let mut rings = rings.iter_many_mut(children);
+        while let Some((mut stroke, mut visibility)) = rings.fetch_next() {
+            stroke.color.set_a(shield.strength / SHIELD_STRENGTH);
+            *visibility = if shield.strength > 0.0 {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+        }
+    }
+}
+
 pub struct PowerupPlugin;
 
 impl Plugin for PowerupPlugin {
@@ -166,15 +626,130 @@ impl Plugin for PowerupPlugin {
         app.register_type::<Option<Debuff>>();
         app.register_type::<Debuff>();
         app.register_type::<Collectible>();
+        app.register_type::<Shield>();
+        app.register_type::<ShieldRing>();
+        app.register_type::<EffectTimers>();
+        app.register_type::<PowerupSpawner>();
+        app.init_resource::<PowerupSpawner>();
+        app.init_resource::<PowerupSpawnTimer>();
         app.replicate::<Collectible>();
+        app.replicate::<Shield>();
+        app.replicate::<EffectTimers>();
         app.replicate::<PowerUp>();
         app.replicate::<Debuff>();
 
         app.add_system(spawn_client_powerup);
         app.add_system(
-            collect_powerups
+            reset_powerup_spawner
+                .run_if(is_server())
+                .in_schedule(OnEnter(GameState::Playing)),
+        );
+        app.add_systems(
+            (
+                collect_powerups,
+                expire_effects,
+                spawn_powerups_over_time,
+                teleport_players,
+                burn_players,
+            )
                 .run_if(is_server())
                 .in_set(OnUpdate(GameState::Playing)),
         );
+        app.add_systems(
+            (
+                spawn_shield_rings,
+                apply_system_buffers,
+                update_shield_rings,
+            )
+                .chain()
+                .run_if(has_window()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::LevelSeed;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn timers_count_down_from_their_end_time() {
+        let timers = EffectTimers {
+            powerup_ends_at: 10.0,
+            debuff_ends_at: 4.0,
+        };
+        assert_eq!(timers.powerup_seconds_left(7.0), 3.0);
+        assert_eq!(timers.debuff_seconds_left(7.0), 0.0);
+    }
+
+    #[test]
+    fn stacking_a_debuff_adds_its_duration() {
+        let mut timers = EffectTimers {
+            debuff_ends_at: 12.0,
+            ..default()
+        };
+        timers.stack_debuff(Debuff::Slowed, 10.0);
+        assert_eq!(timers.debuff_seconds_left(10.0), 8.0);
+    }
+
+    #[test]
+    fn stacking_a_debuff_is_capped() {
+        let mut timers = EffectTimers::default();
+        for _ in 0..5 {
+            timers.stack_debuff(Debuff::Slowed, 0.0);
+        }
+        assert_eq!(
+            timers.debuff_seconds_left(0.0),
+            Debuff::Slowed.duration() * MAX_DEBUFF_STACKS
+        );
+    }
+
+    #[test]
+    fn picking_from_an_empty_table_gives_nothing() {
+        let spawner = PowerupSpawner {
+            table: Vec::new(),
+            ..default()
+        };
+        assert_eq!(spawner.pick(&mut StdRng::seed_from_u64(1)), None);
+    }
+
+    #[test]
+    fn picking_from_a_table_without_weight_gives_nothing() {
+        let spawner = PowerupSpawner {
+            table: vec![PowerupWeight::new(PowerUp::Shield, Debuff::Slowed, 0.0)],
+            ..default()
+        };
+        assert_eq!(spawner.pick(&mut StdRng::seed_from_u64(1)), None);
+    }
+
+    #[test]
+    fn entries_without_weight_are_never_picked() {
+        let spawner = PowerupSpawner {
+            table: vec![
+                PowerupWeight::new(PowerUp::Shield, Debuff::Slowed, 0.0),
+                PowerupWeight::new(PowerUp::RapidFire, Debuff::HealthBurn, 1.0),
+            ],
+            ..default()
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            assert_eq!(
+                spawner.pick(&mut rng),
+                Some((PowerUp::RapidFire, Debuff::HealthBurn))
+            );
+        }
+    }
+
+    #[test]
+    fn level_picks_are_the_same_for_the_same_seed() {
+        let picks = |seed| {
+            let mut rng = LevelSeed::fixed(seed).rng();
+            (0..10)
+                .map(|_| pick_powerup(&LEVEL_POWERUPS, &mut rng))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(picks(7), picks(7));
     }
 }
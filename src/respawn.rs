@@ -0,0 +1,316 @@
+use crate::arena::Arena;
+use crate::game_manager::GameState;
+use crate::health::{update_health_on_damage, DeathEvent};
+use crate::map::{MapDefinition, PlayerSpawn};
+use crate::network::clock::ServerClock;
+use crate::network::{has_window, is_server, NetworkOwner};
+use crate::player::commands::SpawnPlayer;
+use crate::player::{Player, PlayerColor, Players};
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use bevy_replicon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How long a ship that just respawned can't be damaged.
+pub const INVULNERABLE_SECONDS: f32 = 2.0;
+/// How many times a second invulnerable ships blink.
+const BLINK_RATE: f32 = 8.0;
+
+pub struct RespawnPlugin;
+
+impl Plugin for RespawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Invulnerable>();
+        app.replicate::<Invulnerable>();
+        app.init_resource::<RespawnSettings>();
+        app.init_resource::<Respawns>();
+        app.add_system(
+            reset_respawns
+                .run_if(is_server())
+                .in_schedule(OnEnter(GameState::Playing)),
+        );
+        app.add_systems(
+            (
+                queue_respawns.after(update_health_on_damage),
+                respawn_players,
+            )
+                .run_if(is_server())
+                .in_set(OnUpdate(GameState::Playing)),
+        );
+        app.add_system(blink_invulnerable_ships.run_if(has_window()));
+    }
+}
+
+/// How players come back after their ship is destroyed, set by the host.
+#[derive(Resource, Debug, Copy, Clone, PartialEq)]
+pub struct RespawnSettings {
+    /// Ships each player gets per round, 1 means no respawning.
+    pub lives: u32,
+    /// Seconds between a ship being destroyed and the next one spawning.
+    pub delay_seconds: f32,
+}
+
+impl Default for RespawnSettings {
+    fn default() -> Self {
+        Self {
+            lives: 1,
+            delay_seconds: 3.0,
+        }
+    }
+}
+
+/// Players waiting for a new ship, and how many ships everyone lost this round.
+/// Only used on the server.
+#[derive(Resource, Default, Debug)]
+pub struct Respawns {
+    lives_lost: HashMap<PlayerColor, u32>,
+    pending: Vec<PendingRespawn>,
+}
+
+#[derive(Debug)]
+struct PendingRespawn {
+    color: PlayerColor,
+    network_owner: NetworkOwner,
+    respawn_at: f32,
+}
+
+impl Respawns {
+    /// Players that will get a new ship once their delay is up.
+    pub fn pending(&self) -> impl Iterator<Item = PlayerColor> + '_ {
+        self.pending.iter().map(|respawn| respawn.color)
+    }
+}
+
+/// Ships can't be damaged until the [`ServerClock`] reaches `until`.
+/// Replicated so clients can make them blink.
+#[derive(Component, Reflect, Default, Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[reflect(Component, Default)]
+pub struct Invulnerable {
+    pub until: f32,
+}
+
+impl Invulnerable {
+    pub fn after_respawn(now: f32) -> Self {
+        Self {
+            until: now + INVULNERABLE_SECONDS,
+        }
+    }
+
+    pub fn is_active(&self, now: f32) -> bool {
+        now < self.until
+    }
+}
+
+/// Where to put a ship that is coming back: the map spawn inside the arena that is farthest from
+/// any enemy, or the middle of the arena if none of them are inside.
+pub fn safest_spawn(map: &MapDefinition, arena: Option<&Arena>, enemies: &[Vec2]) -> PlayerSpawn {
+    let distance_to_enemies = |spawn: &PlayerSpawn| {
+        enemies
+            .iter()
+            .map(|enemy| enemy.distance(spawn.position))
+            .fold(f32::INFINITY, f32::min)
+    };
+    map.player_spawns
+        .iter()
+        .filter(|spawn| arena.map_or(true, |arena| arena.contains(spawn.position)))
+        .max_by(|a, b| distance_to_enemies(a).total_cmp(&distance_to_enemies(b)))
+        .copied()
+        .unwrap_or_else(|| PlayerSpawn {
+            position: arena.map_or(Vec2::ZERO, |arena| arena.current_center),
+            rotation: 0.0,
+        })
+}
+
+fn reset_respawns(mut respawns: ResMut<Respawns>) {
+    *respawns = Respawns::default();
+}
+
+pub fn queue_respawns(
+    mut respawns: ResMut<Respawns>,
+    mut death_events: EventReader<DeathEvent>,
+    players: Query<(&Player, &NetworkOwner)>,
+    settings: Res<RespawnSettings>,
+    time: Res<Time>,
+) {
+    // A ship is only destroyed once, even if something sends its death twice
+    let mut destroyed = HashSet::new();
+    for event in death_events.iter() {
+        let Ok((player, network_owner)) = players.get(event.entity) else {
+            continue;
+        };
+        if !destroyed.insert(event.entity) {
+            continue;
+        }
+        let lives_lost = respawns.lives_lost.entry(player.color).or_default();
+        *lives_lost += 1;
+        if *lives_lost >= settings.lives {
+            info!("{} is out of lives", player.color);
+            continue;
+        }
+        respawns.pending.push(PendingRespawn {
+            color: player.color,
+            network_owner: *network_owner,
+            respawn_at: time.elapsed_seconds() + settings.delay_seconds,
+        });
+    }
+}
+
+fn respawn_players(
+    mut commands: Commands,
+    mut respawns: ResMut<Respawns>,
+    players: Res<Players>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    respawns.pending.retain(|respawn| {
+        if respawn.respawn_at > now {
+            return true;
+        }
+        // Players that left while waiting don't come back
+        if players.color(respawn.network_owner.0) == Some(respawn.color) {
+            commands.add(SpawnPlayer {
+                color: respawn.color,
+                network_owner: respawn.network_owner,
+                respawn: true,
+            });
+        }
+        false
+    });
+}
+
+fn blink_invulnerable_ships(
+    mut query: Query<(&Invulnerable, &mut Visibility), With<Player>>,
+    clock: Res<ServerClock>,
+    time: Res<Time>,
+) {
+    let now = clock.now(&time);
+    let blink_on = (time.elapsed_seconds() * BLINK_RATE * 2.0) as u32 % 2 == 0;
+    for (invulnerable, mut visibility) in query.iter_mut() {
+        let new_visibility = if invulnerable.is_active(now) && !blink_on {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+        if *visibility != new_visibility {
+            *visibility = new_visibility;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::{ArenaShape, Force};
+    use crate::health::{DamageSource, Health, HealthLostEvent};
+    use crate::player::weapons::DamagedEvent;
+
+    /// Runs the damage and respawn systems with three lives per round.
+    fn respawn_app() -> App {
+        let mut app = App::new();
+        app.add_event::<DamagedEvent>();
+        app.add_event::<DeathEvent>();
+        app.add_event::<HealthLostEvent>();
+        app.init_resource::<ServerClock>();
+        app.init_resource::<Time>();
+        app.init_resource::<Respawns>();
+        app.insert_resource(RespawnSettings {
+            lives: 3,
+            ..default()
+        });
+        app.add_systems((update_health_on_damage, queue_respawns).chain());
+        app
+    }
+
+    fn spawn_ship(app: &mut App) -> Entity {
+        let player = Player {
+            color: PlayerColor::Red,
+            ..default()
+        };
+        app.world
+            .spawn((player, NetworkOwner(1), Health::default()))
+            .id()
+    }
+
+    fn assert_one_life_lost(app: &App) {
+        let respawns = app.world.resource::<Respawns>();
+        assert_eq!(respawns.lives_lost[&PlayerColor::Red], 1);
+        assert_eq!(respawns.pending().count(), 1);
+    }
+
+    fn arena(center: Vec2, size: Vec2) -> Arena {
+        Arena {
+            shape: ArenaShape::Rectangle,
+            starting_size: size,
+            current_size: size,
+            current_center: center,
+            next_size: size,
+            next_center: center,
+            time_spawned: 0.0,
+            friendly_force: Force::None,
+        }
+    }
+
+    #[test]
+    fn several_lethal_hits_in_one_frame_take_one_life() {
+        let mut app = respawn_app();
+        let ship = spawn_ship(&mut app);
+        for _ in 0..2 {
+            app.world.send_event(DamagedEvent {
+                entity: ship,
+                amount: 150.0,
+                source: DamageSource::Arena,
+                normal: None,
+                direction: None,
+                point: None,
+            });
+        }
+        app.update();
+        assert_one_life_lost(&app);
+    }
+
+    #[test]
+    fn repeated_deaths_of_one_ship_take_one_life() {
+        let mut app = respawn_app();
+        let ship = spawn_ship(&mut app);
+        for _ in 0..2 {
+            app.world.send_event(DeathEvent {
+                entity: ship,
+                source: DamageSource::Arena,
+            });
+        }
+        app.update();
+        assert_one_life_lost(&app);
+    }
+
+    #[test]
+    fn respawns_far_away_from_enemies() {
+        let map = MapDefinition::default();
+        let spawn = safest_spawn(&map, None, &[Vec2::new(400.0, 300.0)]);
+        assert_eq!(spawn.position, Vec2::new(-440.0, -350.0));
+    }
+
+    #[test]
+    fn only_respawns_inside_the_arena() {
+        let map = MapDefinition::default();
+        let arena = arena(Vec2::new(400.0, -300.0), Vec2::splat(300.0));
+        let spawn = safest_spawn(&map, Some(&arena), &[Vec2::new(400.0, -300.0)]);
+        assert_eq!(spawn.position, Vec2::new(440.0, -350.0));
+    }
+
+    #[test]
+    fn respawns_in_the_middle_of_the_arena_without_a_spawn_inside() {
+        let map = MapDefinition::default();
+        let arena = arena(Vec2::new(50.0, -20.0), Vec2::splat(100.0));
+        let spawn = safest_spawn(&map, Some(&arena), &[]);
+        assert_eq!(spawn.position, Vec2::new(50.0, -20.0));
+    }
+
+    #[test]
+    fn invulnerability_wears_off() {
+        let invulnerable = Invulnerable::after_respawn(10.0);
+        assert!(invulnerable.is_active(10.0));
+        assert!(invulnerable.is_active(10.0 + INVULNERABLE_SECONDS - 0.1));
+        assert!(!invulnerable.is_active(10.0 + INVULNERABLE_SECONDS));
+        assert!(!Invulnerable::default().is_active(0.0));
+    }
+}
@@ -0,0 +1,441 @@
+use crate::arena::Force;
+use crate::game_manager::{GameEvent, GameState};
+use crate::game_mode::Side;
+use crate::health::{update_health_on_damage, DamageSource, DeathEvent, HealthLostEvent};
+use crate::network::{is_client, is_server};
+use crate::player::{Player, PlayerColor, Players};
+use crate::teams::TeamSettings;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_replicon::prelude::*;
+use bevy_replicon::renet::ServerEvent;
+use serde::{Deserialize, Serialize};
+
+pub struct ScoreboardPlugin;
+
+impl Plugin for ScoreboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Scoreboard>();
+        app.init_resource::<RoundParticipants>();
+        app.add_server_event::<ScoreboardUpdate>();
+        app.add_system(track_round_participants.run_if(is_server()));
+        app.add_system(
+            track_player_stats
+                .after(update_health_on_damage)
+                .run_if(is_server())
+                .in_set(OnUpdate(GameState::Playing)),
+        );
+        app.add_systems(
+            (start_next_match, clear_round_participants)
+                .distributive_run_if(is_server())
+                .in_schedule(OnExit(GameState::PostGame)),
+        );
+        app.add_system(send_scoreboard.run_if(is_server()));
+        app.add_system(receive_scoreboard.run_if(is_client()));
+    }
+}
+
+/// How many rounds make up a match.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize)]
+pub enum MatchLength {
+    /// Play this many rounds, whoever won the most wins the match.
+    Rounds(u32),
+    /// The first to win this many rounds wins the match.
+    FirstTo(u32),
+}
+
+impl Default for MatchLength {
+    fn default() -> Self {
+        MatchLength::FirstTo(3)
+    }
+}
+
+impl std::fmt::Display for MatchLength {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatchLength::Rounds(rounds) => write!(f, "{rounds} rounds"),
+            MatchLength::FirstTo(wins) => write!(f, "First to {wins}"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+pub struct PlayerScore {
+    pub round_wins: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub damage_dealt: f32,
+}
+
+/// Results of the current match.
+/// Kept by the server and sent to clients with [`ScoreboardUpdate`] at the end of every round.
+#[derive(Resource, Debug, Default, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+pub struct Scoreboard {
+    pub length: MatchLength,
+    pub rounds_played: u32,
+    pub scores: HashMap<PlayerColor, PlayerScore>,
+    pub match_over: bool,
+    /// `None` with [`Scoreboard::match_over`] set means the match was a tie.
+    pub match_winner: Option<Side>,
+}
+
+/// Sends the server's [`Scoreboard`] to clients.
+#[derive(Debug, Reflect, Serialize, Deserialize)]
+pub struct ScoreboardUpdate(pub Scoreboard);
+
+impl Scoreboard {
+    pub fn new(length: MatchLength) -> Self {
+        Self {
+            length,
+            ..default()
+        }
+    }
+
+    pub fn score_mut(&mut self, color: PlayerColor) -> &mut PlayerScore {
+        self.scores.entry(color).or_default()
+    }
+
+    /// Credits the winner of a round, then checks whether that decided the match.
+    pub fn record_round(
+        &mut self,
+        outcome: &GameEvent,
+        participants: &RoundParticipants,
+        team_settings: &TeamSettings,
+    ) {
+        match outcome {
+            GameEvent::RoundWon { winner } => self.score_mut(*winner).round_wins += 1,
+            GameEvent::TeamWon { team } => {
+                for color in participants.on_team(*team) {
+                    self.score_mut(color).round_wins += 1;
+                }
+            }
+            _ => {}
+        }
+        self.rounds_played += 1;
+
+        let side_wins = self.side_wins(team_settings);
+        let most_wins = side_wins.values().copied().max().unwrap_or_default();
+        self.match_over = match self.length {
+            MatchLength::Rounds(rounds) => self.rounds_played >= rounds,
+            MatchLength::FirstTo(wins) => most_wins >= wins,
+        };
+        if self.match_over {
+            let mut leaders = side_wins.iter().filter(|(_, wins)| **wins == most_wins);
+            self.match_winner = match (leaders.next(), leaders.next()) {
+                (Some((side, _)), None) => Some(*side),
+                _ => None,
+            };
+        }
+    }
+
+    /// Round wins of each side, every player on a team is credited for the team's wins.
+    fn side_wins(&self, team_settings: &TeamSettings) -> HashMap<Side, u32> {
+        let mut side_wins = HashMap::default();
+        for (color, score) in self.scores.iter() {
+            let team = team_settings.team_for(*color as usize);
+            let wins = side_wins
+                .entry(Side::of(*color, team.as_ref()))
+                .or_default();
+            *wins = score.round_wins.max(*wins);
+        }
+        side_wins
+    }
+
+    /// Every player's score, best players first.
+    pub fn ranking(&self) -> Vec<(PlayerColor, PlayerScore)> {
+        let mut ranking = self
+            .scores
+            .iter()
+            .map(|(color, score)| (*color, *score))
+            .collect::<Vec<_>>();
+        ranking.sort_by(|(_, a), (_, b)| {
+            b.round_wins
+                .cmp(&a.round_wins)
+                .then(b.kills.cmp(&a.kills))
+                .then(a.deaths.cmp(&b.deaths))
+        });
+        ranking
+    }
+
+    /// A line about where the match is at, or who won it.
+    pub fn summary(&self) -> String {
+        if !self.match_over {
+            return format!("{} - after round {}", self.length, self.rounds_played);
+        }
+        match self.match_winner {
+            Some(winner) => format!("{winner} wins the match!"),
+            None => "The match is a tie!".to_string(),
+        }
+    }
+}
+
+/// The players that had a ship in the current round, and the team of that ship.
+/// Only exists on the server, players waiting for the next round aren't in it.
+#[derive(Resource, Default, Debug)]
+pub struct RoundParticipants {
+    teams: HashMap<PlayerColor, Option<Force>>,
+}
+
+impl RoundParticipants {
+    pub fn insert(&mut self, color: PlayerColor, team: Option<Force>) {
+        self.teams.insert(color, team);
+    }
+
+    /// Players that were on `team` this round.
+    pub fn on_team(&self, team: Force) -> impl Iterator<Item = PlayerColor> + '_ {
+        self.teams
+            .iter()
+            .filter(move |(_, player_team)| **player_team == Some(team))
+            .map(|(color, _)| *color)
+    }
+}
+
+fn track_round_participants(
+    mut participants: ResMut<RoundParticipants>,
+    ships: Query<(&Player, Option<&Force>), Added<Player>>,
+) {
+    for (player, team) in ships.iter() {
+        participants.insert(player.color, team.copied());
+    }
+}
+
+fn clear_round_participants(mut participants: ResMut<RoundParticipants>) {
+    *participants = RoundParticipants::default();
+}
+
+fn track_player_stats(
+    mut scoreboard: ResMut<Scoreboard>,
+    mut health_lost_events: EventReader<HealthLostEvent>,
+    mut death_events: EventReader<DeathEvent>,
+    players: Query<&Player>,
+    player_colors: Res<Players>,
+) {
+    let attacker_color = |source: DamageSource| {
+        source
+            .attacker()
+            .and_then(|attacker| player_colors.color(attacker.0))
+    };
+    for event in health_lost_events.iter() {
+        let (Some(attacker), Ok(victim)) =
+            (attacker_color(event.source), players.get(event.entity))
+        else {
+            continue;
+        };
+        if attacker != victim.color {
+            scoreboard.score_mut(attacker).damage_dealt += event.amount;
+        }
+    }
+    for event in death_events.iter() {
+        let Ok(victim) = players.get(event.entity) else {
+            continue;
+        };
+        scoreboard.score_mut(victim.color).deaths += 1;
+        if let Some(attacker) = attacker_color(event.source) {
+            if attacker != victim.color {
+                scoreboard.score_mut(attacker).kills += 1;
+            }
+        }
+    }
+}
+
+/// Starts a fresh scoreboard once the last match has been decided.
+fn start_next_match(mut scoreboard: ResMut<Scoreboard>) {
+    if scoreboard.match_over {
+        *scoreboard = Scoreboard::new(scoreboard.length);
+    }
+}
+
+/// Sends the whole scoreboard once a round has been recorded or a new match started, stats
+/// collected during the round go out with it.
+fn send_scoreboard(
+    scoreboard: Res<Scoreboard>,
+    mut server_events: EventReader<ServerEvent>,
+    mut scoreboard_updates: EventWriter<ToClients<ScoreboardUpdate>>,
+    mut sent_rounds_played: Local<Option<u32>>,
+) {
+    if *sent_rounds_played != Some(scoreboard.rounds_played) {
+        *sent_rounds_played = Some(scoreboard.rounds_played);
+        scoreboard_updates.send(ToClients {
+            mode: SendMode::Broadcast,
+            event: ScoreboardUpdate(scoreboard.clone()),
+        });
+    }
+    // Catch up players that join in the middle of a match
+    for event in server_events.iter() {
+        if let ServerEvent::ClientConnected(client_id, _) = event {
+            scoreboard_updates.send(ToClients {
+                mode: SendMode::Direct(*client_id),
+                event: ScoreboardUpdate(scoreboard.clone()),
+            });
+        }
+    }
+}
+
+fn receive_scoreboard(
+    mut scoreboard: ResMut<Scoreboard>,
+    mut scoreboard_updates: EventReader<ScoreboardUpdate>,
+) {
+    if let Some(update) = scoreboard_updates.iter().last() {
+        *scoreboard = update.0.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::Health;
+    use crate::network::clock::ServerClock;
+    use crate::network::NetworkOwner;
+    use crate::player::weapons::DamagedEvent;
+    use crate::powerup::{Shield, SHIELD_STRENGTH};
+    use crate::respawn::Invulnerable;
+
+    /// Runs the damage and stats systems with Red (client 1) shooting at Blue (client 2).
+    fn stats_app() -> App {
+        let mut app = App::new();
+        app.add_event::<DamagedEvent>();
+        app.add_event::<DeathEvent>();
+        app.add_event::<HealthLostEvent>();
+        app.init_resource::<ServerClock>();
+        app.init_resource::<Time>();
+        app.init_resource::<Scoreboard>();
+        let mut players = Players::default();
+        players.insert(PlayerColor::Red, 1);
+        players.insert(PlayerColor::Blue, 2);
+        app.insert_resource(players);
+        app.add_systems((update_health_on_damage, track_player_stats).chain());
+        app
+    }
+
+    fn spawn_blue(app: &mut App, bundle: impl Bundle) -> Entity {
+        let player = Player {
+            color: PlayerColor::Blue,
+            ..default()
+        };
+        app.world.spawn((player, Health::default(), bundle)).id()
+    }
+
+    fn shoot(app: &mut App, victim: Entity, amount: f32) {
+        app.world.send_event(DamagedEvent {
+            entity: victim,
+            amount,
+            source: DamageSource::Player(NetworkOwner(1)),
+            normal: None,
+            direction: None,
+            point: None,
+        });
+    }
+
+    fn score(app: &mut App, color: PlayerColor) -> PlayerScore {
+        *app.world.resource_mut::<Scoreboard>().score_mut(color)
+    }
+
+    #[test]
+    fn several_lethal_hits_in_one_frame_count_as_one_kill() {
+        let mut app = stats_app();
+        let blue = spawn_blue(&mut app, ());
+        shoot(&mut app, blue, 150.0);
+        shoot(&mut app, blue, 150.0);
+        app.update();
+
+        assert_eq!(score(&mut app, PlayerColor::Red).kills, 1);
+        assert_eq!(score(&mut app, PlayerColor::Blue).deaths, 1);
+        assert_eq!(score(&mut app, PlayerColor::Red).damage_dealt, 100.0);
+    }
+
+    #[test]
+    fn damage_soaked_by_a_shield_is_not_dealt() {
+        let mut app = stats_app();
+        let blue = spawn_blue(&mut app, Shield::full());
+        shoot(&mut app, blue, 100.0);
+        app.update();
+
+        assert_eq!(
+            score(&mut app, PlayerColor::Red).damage_dealt,
+            100.0 - SHIELD_STRENGTH
+        );
+    }
+
+    #[test]
+    fn damage_during_invulnerability_is_not_dealt() {
+        let mut app = stats_app();
+        let blue = spawn_blue(&mut app, Invulnerable::after_respawn(0.0));
+        shoot(&mut app, blue, 50.0);
+        app.update();
+
+        assert_eq!(score(&mut app, PlayerColor::Red).damage_dealt, 0.0);
+    }
+
+    fn free_for_all() -> TeamSettings {
+        TeamSettings::default()
+    }
+
+    fn two_teams() -> TeamSettings {
+        TeamSettings {
+            team_count: 2,
+            ..default()
+        }
+    }
+
+    fn participants(players: &[(PlayerColor, Option<Force>)]) -> RoundParticipants {
+        let mut participants = RoundParticipants::default();
+        for (color, team) in players {
+            participants.insert(*color, *team);
+        }
+        participants
+    }
+
+    #[test]
+    fn team_wins_only_credit_players_in_the_round() {
+        let teams = two_teams();
+        let participants = participants(&[
+            (PlayerColor::Red, Some(Force::Red)),
+            (PlayerColor::Blue, Some(Force::Blue)),
+            (PlayerColor::Green, Some(Force::Red)),
+            (PlayerColor::Purple, Some(Force::Blue)),
+        ]);
+        let mut scoreboard = Scoreboard::new(MatchLength::FirstTo(3));
+        scoreboard.record_round(
+            &GameEvent::TeamWon { team: Force::Red },
+            &participants,
+            &teams,
+        );
+
+        assert_eq!(scoreboard.score_mut(PlayerColor::Red).round_wins, 1);
+        assert_eq!(scoreboard.score_mut(PlayerColor::Green).round_wins, 1);
+        assert_eq!(scoreboard.score_mut(PlayerColor::Blue).round_wins, 0);
+        assert_eq!(scoreboard.score_mut(PlayerColor::Purple).round_wins, 0);
+        assert_eq!(scoreboard.rounds_played, 1);
+        assert!(!scoreboard.match_over);
+    }
+
+    #[test]
+    fn first_to_ends_the_match_once_someone_has_enough_wins() {
+        let teams = free_for_all();
+        let participants = participants(&[(PlayerColor::Red, None), (PlayerColor::Blue, None)]);
+        let red_won = GameEvent::RoundWon {
+            winner: PlayerColor::Red,
+        };
+        let mut scoreboard = Scoreboard::new(MatchLength::FirstTo(2));
+        scoreboard.record_round(&red_won, &participants, &teams);
+        scoreboard.record_round(&GameEvent::Tie, &participants, &teams);
+        assert!(!scoreboard.match_over);
+
+        scoreboard.record_round(&red_won, &participants, &teams);
+        assert!(scoreboard.match_over);
+        assert_eq!(scoreboard.match_winner, Some(Side::Solo(PlayerColor::Red)));
+        assert_eq!(scoreboard.rounds_played, 3);
+    }
+
+    #[test]
+    fn even_round_wins_tie_the_match() {
+        let teams = free_for_all();
+        let participants = participants(&[(PlayerColor::Red, None), (PlayerColor::Blue, None)]);
+        let mut scoreboard = Scoreboard::new(MatchLength::Rounds(2));
+        for winner in [PlayerColor::Red, PlayerColor::Blue] {
+            scoreboard.record_round(&GameEvent::RoundWon { winner }, &participants, &teams);
+        }
+        assert!(scoreboard.match_over);
+        assert_eq!(scoreboard.match_winner, None);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::game_manager::GameState;
+use crate::network::{has_window, is_client};
+use crate::player::prediction::LocallyControlled;
+use crate::player::Player;
+use crate::ui::UiSprite;
+use crate::MainCamera;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_replicon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How much closer the camera is when following a ship than when showing the whole arena.
+const FOLLOW_ZOOM: f32 = 0.6;
+/// How quickly the camera catches up to what it is showing.
+const CAMERA_SPEED: f32 = 4.0;
+
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Spectators>();
+        app.init_resource::<SpectatorCamera>();
+        app.init_resource::<JoinPolicy>();
+        app.add_server_event::<JoinQueued>();
+        app.add_systems((receive_join_queued, clear_join_queue).run_if(is_client()));
+        app.add_systems(
+            (cycle_spectator_target, move_main_camera)
+                .chain()
+                .run_if(has_window()),
+        );
+        app.add_system(reset_spectator_camera.in_schedule(OnExit(GameState::Playing)));
+    }
+}
+
+/// Clients connected to the server that only watch.
+/// They don't get a [`PlayerColor`](crate::player::PlayerColor) or a ship, so they don't count
+/// towards starting or winning a round.
+#[derive(Resource, Default, Debug)]
+pub struct Spectators {
+    clients: HashSet<u64>,
+}
+
+impl Spectators {
+    pub fn insert(&mut self, client_id: u64) {
+        self.clients.insert(client_id);
+    }
+
+    pub fn remove(&mut self, client_id: u64) {
+        self.clients.remove(&client_id);
+    }
+
+    pub fn count(&self) -> usize {
+        self.clients.len()
+    }
+}
+
+/// What happens to players that join while a round is being played.
+#[derive(Resource, clap::ValueEnum, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum JoinPolicy {
+    /// Give them a ship right away.
+    SpawnImmediately,
+    /// Let them spectate until the next round starts, so they can't join a last man standing
+    /// round at full health.
+    #[default]
+    NextRound,
+}
+
+impl JoinPolicy {
+    pub const ALL: [JoinPolicy; 2] = [JoinPolicy::SpawnImmediately, JoinPolicy::NextRound];
+}
+
+impl std::fmt::Display for JoinPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinPolicy::SpawnImmediately => write!(f, "Join right away"),
+            JoinPolicy::NextRound => write!(f, "Wait for the next round"),
+        }
+    }
+}
+
+/// Sent to a client that joined mid round with [`JoinPolicy::NextRound`].
+#[derive(Debug, Reflect, Serialize, Deserialize)]
+pub struct JoinQueued;
+
+/// Exists on the client while it waits for the next round to get a ship.
+#[derive(Resource, Debug)]
+pub struct QueuedForNextRound;
+
+/// What the camera shows while we don't have a ship, either because it was destroyed or we
+/// joined as a spectator.
+#[derive(Resource, Default, Debug)]
+pub struct SpectatorCamera {
+    /// The ship being followed, `None` shows the whole arena.
+    pub target: Option<Entity>,
+}
+
+/// Switches between the remaining ships and the whole arena with the turn left and right inputs.
+fn cycle_spectator_target(
+    mut spectator_camera: ResMut<SpectatorCamera>,
+    local_players: Query<(), (With<Player>, With<LocallyControlled>)>,
+    players: Query<(Entity, &Player)>,
+    keys: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+) {
+    if !local_players.is_empty() {
+        if spectator_camera.target.is_some() {
+            spectator_camera.target = None;
+        }
+        return;
+    }
+
+    // Sorted so cycling goes through the ships in the same order every time
+    let mut ships = players
+        .iter()
+        .map(|(entity, player)| (player.color as usize, entity))
+        .collect::<Vec<_>>();
+    ships.sort();
+    let stops = std::iter::once(None)
+        .chain(ships.into_iter().map(|(_, entity)| Some(entity)))
+        .collect::<Vec<_>>();
+
+    let gamepad_pressed = |button_type| {
+        gamepad_buttons
+            .get_just_pressed()
+            .any(|button| button.button_type == button_type)
+    };
+    let step = if keys.any_just_pressed([KeyCode::Right, KeyCode::D])
+        || gamepad_pressed(GamepadButtonType::DPadRight)
+    {
+        1
+    } else if keys.any_just_pressed([KeyCode::Left, KeyCode::A])
+        || gamepad_pressed(GamepadButtonType::DPadLeft)
+    {
+        -1
+    } else {
+        0
+    };
+
+    // Falls back to the whole arena when the ship we were following is destroyed
+    let current = stops
+        .iter()
+        .position(|stop| *stop == spectator_camera.target)
+        .unwrap_or_default();
+    let next = (current as isize + step).rem_euclid(stops.len() as isize) as usize;
+    if spectator_camera.target != stops[next] {
+        spectator_camera.target = stops[next];
+    }
+}
+
+/// Zooms by scaling the camera's transform rather than its projection, so the sprite the UI is
+/// drawn to can be moved and scaled along with it and stays fixed on screen.
+pub fn move_main_camera(
+    spectator_camera: Res<SpectatorCamera>,
+    ships: Query<&GlobalTransform, With<Player>>,
+    mut cameras: Query<&mut Transform, (With<MainCamera>, Without<UiSprite>)>,
+    mut ui_sprites: Query<&mut Transform, (With<UiSprite>, Without<MainCamera>)>,
+    time: Res<Time>,
+) {
+    let (position, scale) = match spectator_camera
+        .target
+        .and_then(|target| ships.get(target).ok())
+    {
+        Some(ship) => (ship.translation().truncate(), FOLLOW_ZOOM),
+        None => (Vec2::ZERO, 1.0),
+    };
+    let t = (CAMERA_SPEED * time.delta_seconds()).min(1.0);
+    for mut transform in cameras.iter_mut() {
+        let z = transform.translation.z;
+        transform.translation = transform.translation.truncate().lerp(position, t).extend(z);
+        transform.scale = transform.scale.lerp(Vec3::splat(scale), t);
+        for mut ui_sprite in ui_sprites.iter_mut() {
+            let z = ui_sprite.translation.z;
+            ui_sprite.translation = transform.translation.truncate().extend(z);
+            ui_sprite.scale = transform.scale;
+        }
+    }
+}
+
+fn reset_spectator_camera(mut spectator_camera: ResMut<SpectatorCamera>) {
+    spectator_camera.target = None;
+}
+
+fn receive_join_queued(mut commands: Commands, mut events: EventReader<JoinQueued>) {
+    if events.iter().last().is_some() {
+        commands.insert_resource(QueuedForNextRound);
+    }
+}
+
+fn clear_join_queue(mut commands: Commands, local_players: Query<(), Added<LocallyControlled>>) {
+    if !local_players.is_empty() {
+        commands.remove_resource::<QueuedForNextRound>();
+    }
+}
@@ -0,0 +1,61 @@
+use crate::arena::Force;
+use bevy::prelude::*;
+
+/// Forces handed out to players in team modes, in the order teams are filled.
+pub const TEAMS: [Force; 5] = [
+    Force::Red,
+    Force::Blue,
+    Force::Green,
+    Force::Yellow,
+    Force::Pink,
+];
+
+/// How the host split players up, only exists on the server.
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct TeamSettings {
+    /// How many teams to split players into, free for all if less than 2.
+    pub team_count: usize,
+    /// Whether lasers hurt players on the same team.
+    pub friendly_fire: bool,
+    /// The arena takes it easy on this team, see [`FAVORED_FORCE_ZONE_DAMAGE`].
+    /// [`Force::None`] to treat every team the same.
+    pub favored_force: Force,
+}
+
+/// How much of the zone damage players on the arena's favored force take.
+pub const FAVORED_FORCE_ZONE_DAMAGE: f32 = 0.5;
+
+impl Default for TeamSettings {
+    fn default() -> Self {
+        Self {
+            team_count: 0,
+            friendly_fire: false,
+            favored_force: Force::None,
+        }
+    }
+}
+
+impl TeamSettings {
+    pub fn is_team_mode(&self) -> bool {
+        self.team_count >= 2
+    }
+
+    /// The team of the player with this index, players are spread evenly over the teams.
+    /// `None` when playing free for all.
+    pub fn team_for(&self, player_index: usize) -> Option<Force> {
+        if !self.is_team_mode() {
+            return None;
+        }
+        let team_count = self.team_count.min(TEAMS.len());
+        Some(TEAMS[player_index % team_count])
+    }
+
+    /// Whether `attacker` can hurt `target`, players without a team can hurt anyone.
+    pub fn can_damage(&self, attacker: Option<&Force>, target: Option<&Force>) -> bool {
+        match (attacker, target) {
+            (Some(attacker), Some(target)) => self.friendly_fire || attacker != target,
+            _ => true,
+        }
+    }
+}
@@ -1,5 +1,13 @@
-use crate::network::commands::Listen;
+use crate::arena::Force;
+use crate::game_mode::GameModeKind;
+use crate::map::{MapDefinition, Maps, DEFAULT_MAP};
+use crate::network::commands::{Listen, NetworkCommandsExt};
+use crate::network::handshake::truncate_password;
 use crate::network::{NetworkInfo, DEFAULT_PORT};
+use crate::respawn::RespawnSettings;
+use crate::scoreboard::MatchLength;
+use crate::spectator::JoinPolicy;
+use crate::teams::{TeamSettings, TEAMS};
 use bevy::prelude::*;
 use bevy_egui::EguiContexts;
 use egui::{Align2, Color32, Ui, Widget};
@@ -11,7 +19,10 @@ pub fn draw_create_game(
     mut contexts: EguiContexts,
     mut listen_form: Local<ListenForm>,
     network_info: Res<NetworkInfo>,
+    maps: Res<Maps>,
+    map_definitions: Res<Assets<MapDefinition>>,
 ) {
+    let map_names = maps.names(&map_definitions);
     if network_info.is_changed() {
         if let Some(ip) = network_info.public_ip {
             listen_form.ip = ip.to_string();
@@ -22,10 +33,10 @@ pub fn draw_create_game(
         .collapsible(false)
         .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
         .show(contexts.ctx_mut(), |ui| {
-            listen_form.draw(ui);
+            listen_form.draw(ui, &map_names);
             if ui.button("Host").clicked() {
                 if let Ok(listen) = listen_form.validate() {
-                    commands.add(listen);
+                    commands.listen(listen);
                 }
             }
         });
@@ -36,6 +47,16 @@ pub struct ListenForm {
     pub port: u16,
     pub bind: String,
     pub server_name: String,
+    pub secure: bool,
+    pub password: String,
+    pub map: String,
+    /// Maps to rotate through after every round, empty to only play [`ListenForm::map`].
+    pub map_rotation: Vec<String>,
+    pub teams: TeamSettings,
+    pub game_mode: GameModeKind,
+    pub match_length: MatchLength,
+    pub join_policy: JoinPolicy,
+    pub respawn: RespawnSettings,
     pub error: Option<String>,
 }
 
@@ -52,20 +73,146 @@ impl ListenForm {
             })?,
             port: self.port,
             server_name: self.server_name.clone(),
+            secure: self.secure,
+            password: (!self.password.is_empty()).then(|| self.password.clone()),
+            map: Some(self.map.clone()),
+            map_rotation: self.map_rotation.clone(),
+            teams: self.teams.clone(),
+            game_mode: self.game_mode,
+            match_length: self.match_length,
+            join_policy: self.join_policy,
+            respawn: self.respawn,
         })
     }
 
-    pub fn draw(&mut self, ui: &mut Ui) {
+    pub fn draw(&mut self, ui: &mut Ui, map_names: &[String]) {
         ui.heading("Name");
         if ui.text_edit_singleline(&mut self.server_name).changed() {
             self.server_name = self.server_name.replace(|c: char| !c.is_ascii(), "");
             self.server_name = self.server_name.chars().take(18).collect();
             self.error = None;
         }
+        ui.heading("Password (optional)");
+        if ui
+            .add(egui::TextEdit::singleline(&mut self.password).password(true))
+            .changed()
+        {
+            self.password = truncate_password(&self.password).to_string();
+            self.error = None;
+        }
+        ui.heading("Map");
+        egui::ComboBox::from_id_source("map")
+            .selected_text(self.map.as_str())
+            .show_ui(ui, |ui| {
+                for name in map_names {
+                    ui.selectable_value(&mut self.map, name.clone(), name);
+                }
+            });
+        ui.collapsing("Map Rotation", |ui| {
+            ui.label("Switch to the next checked map after every round.");
+            for name in map_names {
+                let mut in_rotation = self.map_rotation.contains(name);
+                if ui.checkbox(&mut in_rotation, name).changed() {
+                    if in_rotation {
+                        self.map_rotation.push(name.clone());
+                    } else {
+                        self.map_rotation.retain(|n| n != name);
+                    }
+                }
+            }
+        });
+        ui.heading("Game Mode");
+        egui::ComboBox::from_id_source("game_mode")
+            .selected_text(self.game_mode.to_string())
+            .show_ui(ui, |ui| {
+                for game_mode in GameModeKind::ALL {
+                    ui.selectable_value(&mut self.game_mode, game_mode, game_mode.to_string());
+                }
+            });
+        ui.heading("Match Length");
+        ui.horizontal(|ui| {
+            let rounds = match self.match_length {
+                MatchLength::Rounds(rounds) | MatchLength::FirstTo(rounds) => rounds,
+            };
+            ui.radio_value(
+                &mut self.match_length,
+                MatchLength::FirstTo(rounds),
+                "First to",
+            );
+            ui.radio_value(
+                &mut self.match_length,
+                MatchLength::Rounds(rounds),
+                "Rounds",
+            );
+            match &mut self.match_length {
+                MatchLength::Rounds(rounds) | MatchLength::FirstTo(rounds) => {
+                    egui::DragValue::new(rounds).clamp_range(1..=99).ui(ui);
+                }
+            }
+        });
+        ui.heading("Lives");
+        ui.horizontal(|ui| {
+            egui::DragValue::new(&mut self.respawn.lives)
+                .clamp_range(1..=99)
+                .ui(ui);
+            if self.respawn.lives > 1 {
+                ui.label("Respawn after");
+                egui::DragValue::new(&mut self.respawn.delay_seconds)
+                    .clamp_range(0.0..=30.0)
+                    .suffix("s")
+                    .ui(ui);
+            }
+        });
+        ui.heading("Players Joining Mid Round");
+        egui::ComboBox::from_id_source("join_policy")
+            .selected_text(self.join_policy.to_string())
+            .show_ui(ui, |ui| {
+                for join_policy in JoinPolicy::ALL {
+                    ui.selectable_value(
+                        &mut self.join_policy,
+                        join_policy,
+                        join_policy.to_string(),
+                    );
+                }
+            });
+        ui.heading("Teams");
+        egui::ComboBox::from_id_source("teams")
+            .selected_text(team_count_label(self.teams.team_count))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.teams.team_count, 0, team_count_label(0));
+                for team_count in 2..=TEAMS.len() {
+                    ui.selectable_value(
+                        &mut self.teams.team_count,
+                        team_count,
+                        team_count_label(team_count),
+                    );
+                }
+            });
+        if self.teams.is_team_mode() {
+            ui.checkbox(&mut self.teams.friendly_fire, "Friendly fire");
+            ui.horizontal(|ui| {
+                ui.label("Favored team")
+                    .on_hover_text("This team takes less damage outside the arena.");
+                egui::ComboBox::from_id_source("favored_team")
+                    .selected_text(self.teams.favored_force.to_string())
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.teams.favored_force, Force::None, "None");
+                        for team in TEAMS.iter().take(self.teams.team_count) {
+                            ui.selectable_value(
+                                &mut self.teams.favored_force,
+                                *team,
+                                team.to_string(),
+                            );
+                        }
+                    });
+            });
+        }
         ui.heading("IP Address");
         if ui.text_edit_singleline(&mut self.ip).changed() {
             self.error = None;
         }
+        ui.checkbox(&mut self.secure, "Require join codes")
+            .on_hover_text("Players can only join with a code you give them.");
         ui.collapsing("Advanced", |ui| {
             ui.heading("Bind IP Address");
             if ui.text_edit_singleline(&mut self.bind).changed() {
@@ -94,7 +241,24 @@ impl FromWorld for ListenForm {
             port: DEFAULT_PORT,
             bind: Ipv4Addr::new(0, 0, 0, 0).to_string(),
             server_name: "My Game".to_string(),
+            secure: false,
+            password: String::new(),
+            map: DEFAULT_MAP.to_string(),
+            map_rotation: Vec::new(),
+            teams: TeamSettings::default(),
+            game_mode: GameModeKind::default(),
+            match_length: MatchLength::default(),
+            join_policy: JoinPolicy::default(),
+            respawn: RespawnSettings::default(),
             error: None,
         }
     }
 }
+
+fn team_count_label(team_count: usize) -> String {
+    if team_count < 2 {
+        "Free for all".to_string()
+    } else {
+        format!("{team_count} teams")
+    }
+}
@@ -0,0 +1,171 @@
+use crate::bundles::lyon_rendering::{get_path_from_verts, LyonRenderBundle};
+use crate::network::clock::ServerClock;
+use crate::player::prediction::LocallyControlled;
+use crate::player::Player;
+use crate::powerup::{Debuff, EffectTimers, PowerUp};
+use crate::MainCamera;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::{ShapeBundle, Stroke};
+
+/// Where the powerup is shown, relative to the middle of the screen. The debuff goes to its right.
+const HUD_POSITION: Vec2 = Vec2::new(-900.0, -480.0);
+const SLOT_SPACING: f32 = 70.0;
+/// Draw the HUD on top of the game but under the menus.
+const HUD_Z: f32 = 998.0;
+
+/// Shows the local player's powerup and debuff with how long they have left.
+///
+/// The icons are paths, so unlike the rest of the UI this is drawn by the main camera and moved
+/// along with it.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct EffectHud;
+
+#[derive(Component, Reflect, Default, Copy, Clone, PartialEq, Eq)]
+#[reflect(Component, Default)]
+pub enum EffectSlot {
+    #[default]
+    PowerUp,
+    Debuff,
+}
+
+/// One icon is spawned for every effect, only the active ones are shown.
+#[derive(Component, Reflect, Copy, Clone, PartialEq, Eq)]
+#[reflect(Component)]
+pub enum EffectIcon {
+    PowerUp(PowerUp),
+    Debuff(Debuff),
+}
+
+pub fn setup_effect_hud(
+    mut commands: Commands,
+    existing: Query<Entity, With<EffectHud>>,
+    asset_server: Res<AssetServer>,
+) {
+    for entity in existing.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let font = asset_server.load("hyperspace_font/Hyperspace Bold.otf");
+    commands
+        .spawn((
+            EffectHud,
+            Name::new("Effect HUD"),
+            SpatialBundle::from_transform(Transform::from_xyz(0.0, 0.0, HUD_Z)),
+        ))
+        .with_children(|parent| {
+            let powerups = [
+                PowerUp::HitherThither,
+                PowerUp::Scattergun,
+                PowerUp::RapidFire,
+                PowerUp::Shield,
+            ]
+            .map(|powerup| (EffectIcon::PowerUp(powerup), powerup.icon()));
+            let debuffs = [
+                Debuff::Slowed,
+                Debuff::Inaccuracy,
+                Debuff::HealthBurn,
+                Debuff::ReversedControls,
+            ]
+            .map(|debuff| (EffectIcon::Debuff(debuff), debuff.icon()));
+
+            for (slot, icons, x, color) in [
+                (EffectSlot::PowerUp, powerups, HUD_POSITION.x, Color::YELLOW),
+                (
+                    EffectSlot::Debuff,
+                    debuffs,
+                    HUD_POSITION.x + SLOT_SPACING,
+                    Color::RED,
+                ),
+            ] {
+                for (effect, path) in icons {
+                    parent.spawn((
+                        effect,
+                        LyonRenderBundle {
+                            shape_render: ShapeBundle {
+                                path: get_path_from_verts(path, Vec2::splat(16.0)),
+                                transform: Transform::from_xyz(x, HUD_POSITION.y, 0.0),
+                                visibility: Visibility::Hidden,
+                                ..default()
+                            },
+                            stroke: Stroke::new(color, 2.0),
+                            ..default()
+                        },
+                    ));
+                }
+                parent.spawn((
+                    slot,
+                    Text2dBundle {
+                        text: Text::from_section(
+                            "",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 18.0,
+                                color,
+                            },
+                        )
+                        .with_alignment(TextAlignment::Center),
+                        transform: Transform::from_xyz(x, HUD_POSITION.y - 34.0, 0.0),
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                ));
+            }
+        });
+}
+
+pub fn update_effect_hud(
+    local_players: Query<(&Player, &EffectTimers), With<LocallyControlled>>,
+    cameras: Query<&Transform, (With<MainCamera>, Without<EffectHud>)>,
+    mut huds: Query<&mut Transform, (With<EffectHud>, Without<MainCamera>)>,
+    mut icons: Query<(&EffectIcon, &mut Visibility)>,
+    mut countdowns: Query<(&EffectSlot, &mut Text, &mut Visibility), Without<EffectIcon>>,
+    clock: Res<ServerClock>,
+    time: Res<Time>,
+) {
+    // Stay in the same spot on screen while the camera follows ships around
+    if let Ok(camera) = cameras.get_single() {
+        for mut hud in huds.iter_mut() {
+            hud.translation = camera.translation.truncate().extend(HUD_Z);
+            hud.scale = camera.scale;
+        }
+    }
+
+    let (powerup, debuff, timers) = match local_players.get_single() {
+        Ok((player, timers)) => (player.powerup, player.debuff, *timers),
+        Err(_) => (None, None, EffectTimers::default()),
+    };
+
+    for (icon, mut visibility) in icons.iter_mut() {
+        let shown = match icon {
+            EffectIcon::PowerUp(icon) => powerup == Some(*icon),
+            EffectIcon::Debuff(icon) => debuff == Some(*icon),
+        };
+        let new_visibility = if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != new_visibility {
+            *visibility = new_visibility;
+        }
+    }
+
+    let now = clock.now(&time);
+    for (slot, mut text, mut visibility) in countdowns.iter_mut() {
+        let seconds_left = match slot {
+            EffectSlot::PowerUp => powerup.map(|_| timers.powerup_seconds_left(now)),
+            EffectSlot::Debuff => debuff.map(|_| timers.debuff_seconds_left(now)),
+        };
+        match seconds_left {
+            Some(seconds_left) => {
+                *visibility = Visibility::Inherited;
+                let value = format!("{:.0}", seconds_left.ceil());
+                if text.sections[0].value != value {
+                    text.sections[0].value = value;
+                }
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
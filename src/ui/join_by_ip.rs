@@ -1,9 +1,12 @@
+use crate::network::auth::decode_join_code;
 use crate::network::commands::Connect;
+use crate::network::handshake::truncate_password;
 use crate::network::DEFAULT_PORT;
 use bevy::prelude::*;
 use bevy_egui::EguiContexts;
 use egui::{Align2, Color32, Ui, Widget};
-use std::net::{AddrParseError, IpAddr};
+use std::error::Error;
+use std::net::IpAddr;
 use std::str::FromStr;
 
 pub fn draw_join_by_ip(
@@ -29,11 +32,24 @@ pub struct ConnectForm {
     pub ip: String,
     pub port: u16,
     pub bind: String,
+    pub join_code: String,
+    pub password: String,
+    pub spectate: bool,
     pub error: Option<String>,
 }
 
 impl ConnectForm {
-    pub fn validate(&mut self) -> Result<Connect, AddrParseError> {
+    pub fn validate(&mut self) -> Result<Connect, Box<dyn Error>> {
+        let join_code = self.join_code.trim();
+        let join_code = if join_code.is_empty() {
+            None
+        } else {
+            decode_join_code(join_code).map_err(|e| {
+                self.error = Some(e.to_string());
+                e
+            })?;
+            Some(join_code.to_string())
+        };
         Ok(Connect {
             bind: IpAddr::from_str(self.bind.as_str()).map_err(|e| {
                 self.error = Some(e.to_string());
@@ -44,6 +60,9 @@ impl ConnectForm {
                 e
             })?,
             port: self.port,
+            join_code,
+            password: (!self.password.is_empty()).then(|| self.password.clone()),
+            spectate: self.spectate,
         })
     }
 
@@ -52,6 +71,19 @@ impl ConnectForm {
         if ui.text_edit_singleline(&mut self.ip).changed() {
             self.error = None;
         }
+        ui.label("Password (if required)");
+        if ui
+            .add(egui::TextEdit::singleline(&mut self.password).password(true))
+            .changed()
+        {
+            self.password = truncate_password(&self.password).to_string();
+            self.error = None;
+        }
+        ui.label("Join Code (if required)");
+        if ui.text_edit_singleline(&mut self.join_code).changed() {
+            self.error = None;
+        }
+        ui.checkbox(&mut self.spectate, "Join as spectator");
         ui.collapsing("Advanced", |ui| {
             ui.label("Bind IP Address");
             if ui.text_edit_singleline(&mut self.bind).changed() {
@@ -75,6 +107,9 @@ impl Default for ConnectForm {
             ip: Connect::default().ip.to_string(),
             port: DEFAULT_PORT,
             bind: Connect::default().bind.to_string(),
+            join_code: String::new(),
+            password: String::new(),
+            spectate: false,
             error: None,
         }
     }
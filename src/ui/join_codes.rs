@@ -0,0 +1,40 @@
+use crate::network::auth::{ConnectTokenIssuer, JOIN_CODE_EXPIRE_SECONDS};
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+use egui::{Align2, Color32};
+
+/// Lets the host of a secure game hand out join codes.
+pub fn draw_join_codes(
+    mut contexts: EguiContexts,
+    issuer: Res<ConnectTokenIssuer>,
+    mut join_code: Local<Option<String>>,
+    mut error: Local<Option<String>>,
+) {
+    egui::Window::new("Join Codes")
+        .auto_sized()
+        .collapsible(false)
+        .anchor(Align2::CENTER_BOTTOM, (0.0, -40.0))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!(
+                "Each code lets one player join and expires after {} minutes.",
+                JOIN_CODE_EXPIRE_SECONDS / 60
+            ));
+            if ui.button("New Join Code").clicked() {
+                match issuer.issue_join_code() {
+                    Ok(issued) => {
+                        *join_code = Some(issued.code);
+                        *error = None;
+                    }
+                    Err(e) => *error = Some(e.to_string()),
+                }
+            }
+            if let Some(code) = join_code.as_ref() {
+                if ui.button("Copy to Clipboard").clicked() {
+                    ui.output_mut(|o| o.copied_text = code.clone());
+                }
+            }
+            if let Some(error_message) = error.as_ref() {
+                ui.colored_label(Color32::RED, error_message);
+            }
+        });
+}
@@ -0,0 +1,108 @@
+use crate::health::{KillCause, KillEvent};
+use bevy::prelude::*;
+
+/// How long a kill stays in the feed.
+const KILL_FEED_SECONDS: f64 = 6.0;
+const MAX_KILL_FEED_ENTRIES: usize = 5;
+
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct KillFeed;
+
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct KillFeedEntry {
+    expires_at: f64,
+}
+
+pub fn setup_kill_feed(mut commands: Commands, existing: Query<Entity, With<KillFeed>>) {
+    if !existing.is_empty() {
+        return;
+    }
+    commands.spawn((
+        KillFeed,
+        Name::new("Kill Feed"),
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(12.0),
+                    bottom: Val::Px(8.0),
+                    ..default()
+                },
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::FlexEnd,
+                ..default()
+            },
+            ..default()
+        },
+    ));
+}
+
+pub fn update_kill_feed(
+    mut commands: Commands,
+    mut kill_events: EventReader<KillEvent>,
+    feeds: Query<Entity, With<KillFeed>>,
+    entries: Query<(Entity, &KillFeedEntry)>,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+) {
+    let Ok(feed) = feeds.get_single() else {
+        return;
+    };
+
+    let mut live_entries = Vec::new();
+    for (entity, entry) in entries.iter() {
+        if entry.expires_at <= time.elapsed_seconds_f64() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            live_entries.push((entry.expires_at, entity));
+        }
+    }
+
+    let font = asset_server.load("hyperspace_font/Hyperspace Bold.otf");
+    let section = |text: String, color: Color| {
+        TextSection::new(
+            text,
+            TextStyle {
+                font: font.clone(),
+                font_size: 18.0,
+                color,
+            },
+        )
+    };
+    for event in kill_events.iter() {
+        let victim = section(event.victim.to_string(), event.victim.color());
+        let sections = match event.cause {
+            KillCause::Player(killer) => vec![
+                section(killer.to_string(), killer.color()),
+                section(" destroyed ".to_string(), Color::GRAY),
+                victim,
+            ],
+            KillCause::Arena => vec![
+                victim,
+                section(" was lost outside the arena".to_string(), Color::GRAY),
+            ],
+            KillCause::Asteroid => vec![
+                victim,
+                section(" crashed into an asteroid".to_string(), Color::GRAY),
+            ],
+            KillCause::HealthBurn => vec![victim, section(" burned up".to_string(), Color::GRAY)],
+        };
+        let expires_at = time.elapsed_seconds_f64() + KILL_FEED_SECONDS;
+        let entry = commands
+            .spawn((
+                KillFeedEntry { expires_at },
+                TextBundle::from_sections(sections),
+            ))
+            .id();
+        commands.entity(feed).add_child(entry);
+        live_entries.push((expires_at, entry));
+    }
+
+    // Drop the oldest kills if too many happen at once
+    live_entries.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (_, entity) in live_entries.iter().skip(MAX_KILL_FEED_ENTRIES) {
+        commands.entity(*entity).despawn_recursive();
+    }
+}
@@ -1,9 +1,14 @@
-use crate::network::commands::NetworkCommandsExt;
+use crate::game_manager::GameState;
+use crate::network::commands::{Connect, NetworkCommandsExt};
+use crate::network::handshake::{truncate_password, ConnectionError};
 use crate::network::matchmaking::ServerList;
+use crate::network::protocol::GAME_VERSION;
 use crate::network::DEFAULT_PORT;
 use crate::ui::{change_button_text_color, ChangeStateOnClick, Menu, MenuUiContainer};
 use bevy::ecs::system::EntityCommands;
 use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+use egui::Align2;
 use std::net::{IpAddr, Ipv4Addr};
 use std::str::FromStr;
 
@@ -123,6 +128,16 @@ pub fn setup_lobby_browser(
 #[reflect(Component, Default)]
 pub struct JoinGameButton {
     pub ip: String,
+    pub has_password: bool,
+    pub compatible: bool,
+    pub version: String,
+}
+
+/// Exists while asking for the password of a lobby the player clicked join on.
+#[derive(Resource, Default, Debug)]
+pub struct PasswordPrompt {
+    pub ip: String,
+    pub password: String,
 }
 
 pub fn handle_join_game_click(
@@ -130,12 +145,24 @@ pub fn handle_join_game_click(
     mut query: Query<(Entity, &Interaction, &JoinGameButton), Changed<Interaction>>,
     children: Query<&Children>,
     mut texts: Query<&mut Text>,
+    mut next_game_state: ResMut<NextState<GameState>>,
 ) {
     for (entity, interaction, join_game) in query.iter_mut() {
         match interaction {
             Interaction::Clicked => {
-                if let Ok(ip) = IpAddr::from_str(&join_game.ip) {
-                    commands.connect(ip, Ipv4Addr::new(0, 0, 0, 0).into(), DEFAULT_PORT);
+                if !join_game.compatible {
+                    commands.insert_resource(ConnectionError(format!(
+                        "Version mismatch. The server is running {} and you have {}",
+                        join_game.version, GAME_VERSION
+                    )));
+                    next_game_state.set(GameState::PreGame);
+                } else if join_game.has_password {
+                    commands.insert_resource(PasswordPrompt {
+                        ip: join_game.ip.clone(),
+                        ..default()
+                    });
+                } else if let Ok(ip) = IpAddr::from_str(&join_game.ip) {
+                    commands.connect(ip, Ipv4Addr::new(0, 0, 0, 0).into(), DEFAULT_PORT, false);
                 };
             }
             Interaction::Hovered => {
@@ -166,8 +193,10 @@ pub fn update_lobby_browser(
             font.clone(),
             vec![
                 (250.0, "Name".to_string()),
+                (150.0, "Map".to_string()),
                 (100.0, "Players".to_string()),
                 (100.0, "Password".to_string()),
+                (100.0, "Version".to_string()),
                 (100.0, "".to_string()),
             ],
         );
@@ -177,11 +206,20 @@ pub fn update_lobby_browser(
                 font.clone(),
                 vec![
                     (250.0, lobby.name.to_string()),
+                    (150.0, lobby.map.to_string()),
                     (
                         100.0,
                         format!("{} / {}", lobby.slots_occupied, lobby.player_capacity),
                     ),
                     (100.0, lobby.has_password.to_string()),
+                    (
+                        100.0,
+                        if lobby.version.is_empty() {
+                            "?".to_string()
+                        } else {
+                            lobby.version.clone()
+                        },
+                    ),
                 ],
             )
             .with_children(|child_builder| {
@@ -198,12 +236,19 @@ pub fn update_lobby_browser(
                         },
                         JoinGameButton {
                             ip: lobby.ip.clone(),
+                            has_password: lobby.has_password,
+                            compatible: lobby.is_compatible(),
+                            version: lobby.version.clone(),
                         },
                     ))
                     .with_children(|cb| {
                         cb.spawn(TextBundle {
                             text: Text::from_section(
-                                "Join",
+                                if lobby.is_compatible() {
+                                    "Join"
+                                } else {
+                                    "Outdated"
+                                },
                                 TextStyle {
                                     font: font.clone(),
                                     font_size: 24.0,
@@ -218,6 +263,41 @@ pub fn update_lobby_browser(
     });
 }
 
+pub fn draw_password_prompt(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut prompt: ResMut<PasswordPrompt>,
+) {
+    egui::Window::new("Password Required")
+        .auto_sized()
+        .collapsible(false)
+        .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("Password");
+            if ui
+                .add(egui::TextEdit::singleline(&mut prompt.password).password(true))
+                .changed()
+            {
+                prompt.password = truncate_password(&prompt.password).to_string();
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Join").clicked() {
+                    if let Ok(ip) = IpAddr::from_str(&prompt.ip) {
+                        commands.add(Connect {
+                            ip,
+                            password: Some(prompt.password.clone()),
+                            ..default()
+                        });
+                    }
+                    commands.remove_resource::<PasswordPrompt>();
+                }
+                if ui.button("Cancel").clicked() {
+                    commands.remove_resource::<PasswordPrompt>();
+                }
+            });
+        });
+}
+
 fn row_builder<'w, 's, 'a>(
     child_builder: &'a mut ChildBuilder<'w, 's, '_>,
     font: Handle<Font>,
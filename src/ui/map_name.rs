@@ -0,0 +1,44 @@
+use crate::level::Level;
+use bevy::prelude::*;
+
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct MapNameText;
+
+/// Shows the name of the map being played at the top of the screen.
+pub fn show_map_name(
+    mut commands: Commands,
+    levels: Query<&Level, Added<Level>>,
+    existing: Query<Entity, With<MapNameText>>,
+    asset_server: Res<AssetServer>,
+) {
+    for level in levels.iter() {
+        for entity in existing.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        commands.spawn((
+            MapNameText,
+            Name::new("Map Name"),
+            TextBundle {
+                text: Text::from_section(
+                    level.map.clone(),
+                    TextStyle {
+                        font: asset_server.load("hyperspace_font/Hyperspace Bold.otf"),
+                        font_size: 18.0,
+                        color: Color::GRAY,
+                    },
+                ),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(12.0),
+                        top: Val::Px(8.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                ..default()
+            },
+        ));
+    }
+}
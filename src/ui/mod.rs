@@ -1,23 +1,46 @@
 mod confirm_quit;
 mod create_game;
+mod effects;
 mod focus;
 mod health_bar;
 mod join_by_ip;
+mod join_codes;
+mod kill_feed;
 mod lobby_browser;
 mod main_menu;
+mod map_name;
 mod pre_game;
+mod round_status;
+mod spectator;
+mod zone_warning;
 
 use crate::game_manager::{GameState, Persist};
+use crate::network::auth::ConnectTokenIssuer;
 use crate::network::commands::Disconnect;
 use crate::network::matchmaking::{MatchmakingState, ServerList};
+use crate::spectator::move_main_camera;
 use crate::ui::confirm_quit::{confirm_quit_to_menu_update, setup_confirm_quit};
 use crate::ui::create_game::draw_create_game;
+use crate::ui::effects::{setup_effect_hud, update_effect_hud, EffectHud, EffectIcon, EffectSlot};
 use crate::ui::focus::ui_focus_system;
 use crate::ui::health_bar::{setup_health_bar, update_health_bar};
 use crate::ui::join_by_ip::draw_join_by_ip;
-use crate::ui::lobby_browser::{handle_join_game_click, setup_lobby_browser, update_lobby_browser};
+use crate::ui::join_codes::draw_join_codes;
+use crate::ui::kill_feed::{setup_kill_feed, update_kill_feed, KillFeed, KillFeedEntry};
+use crate::ui::lobby_browser::{
+    draw_password_prompt, handle_join_game_click, setup_lobby_browser, update_lobby_browser,
+    PasswordPrompt,
+};
 use crate::ui::main_menu::setup_main_menu;
+use crate::ui::map_name::{show_map_name, MapNameText};
 use crate::ui::pre_game::{setup_pre_game, update_pre_game_text};
+use crate::ui::round_status::{
+    spawn_round_status, update_round_status, HillMarker, RoundStatusText,
+};
+use crate::ui::spectator::{setup_spectator_text, update_spectator_text, SpectatorText};
+use crate::ui::zone_warning::{
+    setup_zone_warning, update_zone_warning, ZoneIndicator, ZoneWarningEdge, ZoneWarningText,
+};
 use crate::MainCamera;
 use bevy::core_pipeline::clear_color::ClearColorConfig;
 use bevy::core_pipeline::core_2d;
@@ -81,8 +104,18 @@ impl Plugin for UiPlugin {
                 .in_set(OnUpdate(Menu::LobbyBrowser)),
         ));
 
+        app.add_system(
+            draw_password_prompt
+                .run_if(resource_exists::<PasswordPrompt>())
+                .in_set(OnUpdate(Menu::LobbyBrowser)),
+        );
         app.add_systems((draw_create_game.in_set(OnUpdate(Menu::CreateGame)),));
         app.add_systems((draw_join_by_ip.in_set(OnUpdate(Menu::JoinByIP)),));
+        app.add_system(
+            draw_join_codes
+                .run_if(resource_exists::<ConnectTokenIssuer>())
+                .run_if(in_state(Menu::PreGame).or_else(in_state(Menu::ConfirmQuitToMain))),
+        );
 
         app.add_system(
             confirm_quit_to_menu_update
@@ -106,7 +139,40 @@ impl Plugin for UiPlugin {
         app.add_system(resize_ui.run_if(on_event::<WindowResized>()));
         app.add_system(button_hover);
         app.add_system(update_health_bar);
+        app.register_type::<MapNameText>();
+        app.add_system(show_map_name);
+        app.register_type::<RoundStatusText>();
+        app.register_type::<HillMarker>();
+        app.add_systems(
+            (
+                spawn_round_status,
+                apply_system_buffers,
+                update_round_status,
+            )
+                .chain(),
+        );
         app.add_system(setup_health_bar.in_schedule(OnEnter(GameState::Playing)));
+        app.register_type::<ZoneWarningText>();
+        app.register_type::<ZoneWarningEdge>();
+        app.register_type::<ZoneIndicator>();
+        app.add_system(setup_zone_warning.in_schedule(OnEnter(GameState::Playing)));
+        app.register_type::<KillFeed>();
+        app.register_type::<KillFeedEntry>();
+        app.add_system(setup_kill_feed.in_schedule(OnEnter(GameState::Playing)));
+        app.add_system(update_kill_feed);
+        app.register_type::<SpectatorText>();
+        app.add_system(setup_spectator_text.in_schedule(OnEnter(GameState::Playing)));
+        app.add_system(update_spectator_text.in_set(OnUpdate(GameState::Playing)));
+        app.register_type::<EffectHud>();
+        app.register_type::<EffectSlot>();
+        app.register_type::<EffectIcon>();
+        app.add_system(setup_effect_hud.in_schedule(OnEnter(GameState::Playing)));
+        app.add_system(
+            update_effect_hud
+                .after(move_main_camera)
+                .in_set(OnUpdate(GameState::Playing)),
+        );
+        app.add_system(update_zone_warning);
     }
 }
 
@@ -1,4 +1,5 @@
 use crate::network::commands::Disconnect;
+use crate::network::handshake::ConnectionError;
 use crate::ui::{CommandOnClick, Menu, MenuUiContainer};
 use bevy::prelude::*;
 use bevy_replicon::prelude::{RenetClient, RenetServer};
@@ -80,9 +81,12 @@ pub fn update_pre_game_text(
     mut query: Query<&mut Text, With<PreGameText>>,
     server: Option<Res<RenetServer>>,
     client: Option<Res<RenetClient>>,
+    connection_error: Option<Res<ConnectionError>>,
 ) {
     for mut text in query.iter_mut() {
-        text.sections[0].value = if server.is_some() {
+        text.sections[0].value = if let Some(connection_error) = &connection_error {
+            format!("Could not join: {}", connection_error.0)
+        } else if server.is_some() {
             "Waiting for Players".to_string()
         } else if client
             .as_ref()
@@ -0,0 +1,88 @@
+use crate::arena::ArenaShape;
+use crate::bundles::lyon_rendering::LyonRenderBundle;
+use crate::game_mode::RoundStatus;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::{Path, ShapeBundle, Stroke};
+
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct RoundStatusText;
+
+/// Outline of the hill in king of the hill, a child of the arena so it follows its center.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct HillMarker;
+
+pub fn spawn_round_status(
+    mut commands: Commands,
+    statuses: Query<Entity, Added<RoundStatus>>,
+    existing: Query<Entity, With<RoundStatusText>>,
+    asset_server: Res<AssetServer>,
+) {
+    for arena in statuses.iter() {
+        for entity in existing.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        commands.spawn((
+            RoundStatusText,
+            Name::new("Round Status"),
+            TextBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("hyperspace_font/Hyperspace Bold.otf"),
+                        font_size: 18.0,
+                        color: Color::GRAY,
+                    },
+                )
+                .with_alignment(TextAlignment::Right),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        right: Val::Px(12.0),
+                        top: Val::Px(8.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                ..default()
+            },
+        ));
+        let hill = commands
+            .spawn((
+                HillMarker,
+                Name::new("Hill"),
+                LyonRenderBundle {
+                    shape_render: ShapeBundle {
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    stroke: Stroke::new(Color::GOLD, 2.0),
+                    ..default()
+                },
+            ))
+            .id();
+        commands.entity(arena).add_child(hill);
+    }
+}
+
+pub fn update_round_status(
+    statuses: Query<&RoundStatus, Changed<RoundStatus>>,
+    mut texts: Query<&mut Text, With<RoundStatusText>>,
+    mut hills: Query<(&mut Path, &mut Visibility), With<HillMarker>>,
+) {
+    for status in statuses.iter() {
+        for mut text in texts.iter_mut() {
+            text.sections[0].value = status.summary.clone();
+        }
+        for (mut path, mut visibility) in hills.iter_mut() {
+            match status.hill_radius {
+                Some(radius) => {
+                    *path = ArenaShape::Circle.path(Vec2::splat(radius * 2.0));
+                    *visibility = Visibility::Inherited;
+                }
+                None => *visibility = Visibility::Hidden,
+            }
+        }
+    }
+}
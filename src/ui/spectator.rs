@@ -0,0 +1,79 @@
+use crate::player::prediction::LocallyControlled;
+use crate::player::Player;
+use crate::spectator::{QueuedForNextRound, SpectatorCamera};
+use bevy::prelude::*;
+
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct SpectatorText;
+
+pub fn setup_spectator_text(
+    mut commands: Commands,
+    existing: Query<Entity, With<SpectatorText>>,
+    asset_server: Res<AssetServer>,
+) {
+    for entity in existing.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.spawn((
+        SpectatorText,
+        Name::new("Spectator Text"),
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("hyperspace_font/Hyperspace Bold.otf"),
+                    font_size: 20.0,
+                    color: Color::GRAY,
+                },
+            )
+            .with_alignment(TextAlignment::Center),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Percent(30.0),
+                    bottom: Val::Px(8.0),
+                    ..default()
+                },
+                size: Size::width(Val::Percent(40.0)),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+}
+
+/// Tells players without a ship who they are watching and how to switch.
+pub fn update_spectator_text(
+    spectator_camera: Res<SpectatorCamera>,
+    local_players: Query<(), (With<Player>, With<LocallyControlled>)>,
+    players: Query<&Player>,
+    queued: Option<Res<QueuedForNextRound>>,
+    mut texts: Query<(&mut Text, &mut Visibility), With<SpectatorText>>,
+) {
+    for (mut text, mut visibility) in texts.iter_mut() {
+        if !local_players.is_empty() {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+        let section = &mut text.sections[0];
+        match spectator_camera
+            .target
+            .and_then(|target| players.get(target).ok())
+        {
+            Some(player) => {
+                section.value = format!("Spectating {}  < / > to switch", player.color);
+                section.style.color = player.color.color();
+            }
+            None => {
+                section.value = "Spectating  < / > to follow a ship".to_string();
+                section.style.color = Color::GRAY;
+            }
+        }
+        if queued.is_some() {
+            section.value = format!("You'll join next round\n{}", section.value);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use crate::arena::{Arena, ZoneWarning, ZONE_MAX_DAMAGE};
+use crate::bundles::lyon_rendering::ship_parts::ZONE_INDICATOR;
+use crate::bundles::lyon_rendering::{get_path_from_verts, LyonRenderBundle};
+use crate::network::clock::ServerClock;
+use crate::player::prediction::LocallyControlled;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::{ShapeBundle, Stroke};
+
+/// How far from the ship the indicator pointing back to the arena is drawn.
+const INDICATOR_DISTANCE: f32 = 48.0;
+/// Thickness of the red glow along the edges of the screen.
+const EDGE_THICKNESS: f32 = 32.0;
+
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct ZoneWarningText;
+
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct ZoneWarningEdge;
+
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct ZoneIndicator;
+
+pub fn setup_zone_warning(
+    mut commands: Commands,
+    existing: Query<
+        Entity,
+        Or<(
+            With<ZoneWarningText>,
+            With<ZoneWarningEdge>,
+            With<ZoneIndicator>,
+        )>,
+    >,
+    asset_server: Res<AssetServer>,
+) {
+    for entity in existing.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    commands.spawn((
+        ZoneWarningText,
+        Name::new("Zone Warning"),
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("hyperspace_font/Hyperspace Bold.otf"),
+                    font_size: 32.0,
+                    color: Color::RED,
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Percent(30.0),
+                    top: Val::Percent(15.0),
+                    ..default()
+                },
+                size: Size::width(Val::Percent(40.0)),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        }
+        .with_text_alignment(TextAlignment::Center),
+    ));
+
+    let edges = [
+        (
+            UiRect {
+                left: Val::Px(0.0),
+                top: Val::Px(0.0),
+                ..default()
+            },
+            Size::new(Val::Percent(100.0), Val::Px(EDGE_THICKNESS)),
+        ),
+        (
+            UiRect {
+                left: Val::Px(0.0),
+                bottom: Val::Px(0.0),
+                ..default()
+            },
+            Size::new(Val::Percent(100.0), Val::Px(EDGE_THICKNESS)),
+        ),
+        (
+            UiRect {
+                left: Val::Px(0.0),
+                top: Val::Px(0.0),
+                ..default()
+            },
+            Size::new(Val::Px(EDGE_THICKNESS), Val::Percent(100.0)),
+        ),
+        (
+            UiRect {
+                right: Val::Px(0.0),
+                top: Val::Px(0.0),
+                ..default()
+            },
+            Size::new(Val::Px(EDGE_THICKNESS), Val::Percent(100.0)),
+        ),
+    ];
+    for (position, size) in edges {
+        commands.spawn((
+            ZoneWarningEdge,
+            Name::new("Zone Warning Edge"),
+            NodeBundle {
+                background_color: BackgroundColor::from(Color::NONE),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position,
+                    size,
+                    ..default()
+                },
+                ..default()
+            },
+        ));
+    }
+
+    commands.spawn((
+        ZoneIndicator,
+        Name::new("Zone Indicator"),
+        LyonRenderBundle {
+            shape_render: ShapeBundle {
+                path: get_path_from_verts(&ZONE_INDICATOR, Vec2::splat(12.0)),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            stroke: Stroke::new(Color::RED, 2.0),
+            ..default()
+        },
+    ));
+}
+
+/// Counts down until the zone starts hurting the local player, and points them back to the arena.
+pub fn update_zone_warning(
+    players: Query<(&ZoneWarning, &Transform), With<LocallyControlled>>,
+    arenas: Query<&Transform, (With<Arena>, Without<LocallyControlled>)>,
+    mut texts: Query<(&mut Text, &mut Visibility), With<ZoneWarningText>>,
+    mut edges: Query<&mut BackgroundColor, With<ZoneWarningEdge>>,
+    mut indicators: Query<
+        (&mut Transform, &mut Visibility),
+        (
+            With<ZoneIndicator>,
+            Without<ZoneWarningText>,
+            Without<Arena>,
+            Without<LocallyControlled>,
+        ),
+    >,
+    clock: Res<ServerClock>,
+    time: Res<Time>,
+) {
+    let now = clock.now(&time);
+    let warning = players
+        .get_single()
+        .ok()
+        .filter(|(warning, _)| warning.is_outside);
+
+    let visibility = if warning.is_some() {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+
+    for (mut text, mut text_visibility) in texts.iter_mut() {
+        *text_visibility = visibility;
+        if let Some((warning, _)) = warning {
+            let seconds_until_damage = warning.seconds_until_damage(now);
+            text.sections[0].value = if seconds_until_damage > 0.0 {
+                format!("Return to the arena! {:.1}", seconds_until_damage)
+            } else {
+                format!(
+                    "Outside the arena! -{:.0} HP/s",
+                    warning.damage_per_second(now)
+                )
+            };
+        }
+    }
+
+    // Glow brighter the more damage the zone is doing, pulsing so it catches the eye
+    let alpha = warning.map_or(0.0, |(warning, _)| {
+        let intensity = 0.15 + 0.35 * (warning.damage_per_second(now) / ZONE_MAX_DAMAGE);
+        let pulse = 0.5 + 0.5 * (time.elapsed_seconds() * 6.0).sin();
+        intensity * (0.6 + 0.4 * pulse)
+    });
+    for mut edge in edges.iter_mut() {
+        edge.0 = Color::rgba(1.0, 0.0, 0.0, alpha);
+    }
+
+    for (mut transform, mut indicator_visibility) in indicators.iter_mut() {
+        let target = warning.zip(arenas.get_single().ok());
+        let Some(((_, player_transform), arena_transform)) = target else {
+            *indicator_visibility = Visibility::Hidden;
+            continue;
+        };
+        let direction = (arena_transform.translation.xy() - player_transform.translation.xy())
+            .try_normalize()
+            .unwrap_or(Vec2::Y);
+        *indicator_visibility = Visibility::Inherited;
+        transform.translation =
+            (player_transform.translation.xy() + direction * INDICATOR_DISTANCE).extend(1.0);
+        transform.rotation = Quat::from_rotation_arc_2d(Vec2::Y, direction);
+    }
+}